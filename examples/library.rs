@@ -0,0 +1,24 @@
+//! Embed, read back and remove a message without going through the CLI.
+//!
+//! Run with `cargo run --example library [PNG_FILE]`.
+
+use std::fs;
+
+use pngme::{decode_message, encode_message, remove_message, Png, Result};
+
+fn main() -> Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("example.png"));
+    let contents = fs::read(&path)?;
+    let mut png = Png::try_from(contents.as_slice())?;
+
+    encode_message(&mut png, "exMp", "This is a secret message!")?;
+    println!("Encoded: {}", decode_message(&png, "exMp", false)?);
+
+    let removed = remove_message(&mut png, "exMp", false)?;
+    println!("Removed {} byte message", removed.length());
+    println!("PNG is back to {} bytes", png.as_bytes().len());
+
+    Ok(())
+}
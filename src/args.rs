@@ -1,36 +1,362 @@
-use clap::{arg, Command};
+use clap::{arg, value_parser, Arg, ArgAction, ArgGroup, Command};
 
 pub fn cli() -> Command {
     Command::new("pngme")
         .about("Put a secret message into a PNG file")
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(arg!(-y --"assume-yes" "Overwrite files without asking for confirmation").global(true))
+        .arg(
+            arg!(--"verify-after-write" "Re-read each written PNG file to check that it parses")
+                .global(true),
+        )
+        .arg(
+            arg!(--"chunk-limit-warn" <N> "Warn when a PNG file has more than N ancillary chunks")
+                .value_parser(value_parser!(usize))
+                .default_value("50")
+                .global(true),
+        )
+        .arg(arg!(--"no-warnings" "Don't print advisory warnings").global(true))
+        .arg(arg!(-q --quiet "Don't print summaries or other status lines").global(true))
+        .arg(
+            arg!(--"keep-crc" "Accept chunks with wrong CRCs and write their CRCs back unchanged")
+                .global(true),
+        )
+        .arg(arg!(--finalize "Add a terminating IEND chunk before writing if it is missing").global(true))
+        .arg(
+            arg!(--"max-file-size" <BYTES> "Refuse to load a PNG file larger than BYTES into memory")
+                .value_parser(value_parser!(u64))
+                .default_value("2147483648")
+                .global(true),
+        )
         .subcommand(
             Command::new("encode")
                 .about("Encode secret message in PNG file")
-                .arg(arg!(<PATH> "The PNG file to encode"))
-                .arg(arg!(<CHUNK_TYPE> "The 4 byte chunk type code"))
-                .arg(arg!(<MESSAGE> "The secret message to encode"))
+                .arg(arg!(<PATH> "The PNG file to encode, or - to read from stdin and write to stdout"))
+                .arg(arg!(<CHUNK_TYPE> "The 4 byte chunk type code, or a label with --as-private"))
+                .arg(arg!([MESSAGE] "The secret message to encode"))
+                .arg(output_arg())
+                .arg(arg!(--"data-hex" <HEX> "Use these hex-encoded bytes as the chunk data"))
+                .arg(arg!(--"data-stdin-hex" "Read hex-encoded chunk data from stdin"))
+                .arg(arg!(--"message-file" <FILE> "Use the bytes of FILE as the message"))
+                .arg(arg!(--"template-message" <TEMPLATE> "Use TEMPLATE as the message, filling in {filename}, {date}, {width} and {height}"))
+                .arg(
+                    arg!(--named <PAIR> "Pack a KEY=VALUE message into the chunk alongside any others given")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    arg!(--"as-text" <KEYWORD> "Store the message as a tEXt chunk under KEYWORD")
+                        .conflicts_with_all(["data-hex", "data-stdin-hex", "named", "message-file", "template-message"]),
+                )
+                .group(
+                    ArgGroup::new("data")
+                        .args(["MESSAGE", "data-hex", "data-stdin-hex", "named", "message-file", "template-message"])
+                        .required(true),
+                )
+                .arg(
+                    arg!(--"normalize-type-case" <KIND> "Case CHUNK_TYPE into a private, safe-to-copy type")
+                        .value_parser(["ancillary", "critical"])
+                        .conflicts_with("as-text"),
+                )
+                .arg(
+                    arg!(--"as-private" "Make a private, ancillary, safe-to-copy chunk type from the label in CHUNK_TYPE and print it")
+                        .conflicts_with_all(["normalize-type-case", "as-text"]),
+                )
+                .arg(codec_arg("Transform the chunk data with CODEC before storing it").conflicts_with("as-text"))
+                .arg(
+                    arg!(--compress "Compress the message with zlib, marked by a magic prefix")
+                        .conflicts_with("as-text"),
+                )
+                .arg(
+                    arg!(--password <PASS> "Encrypt the message with AES-256-GCM under a key derived from PASS")
+                        .conflicts_with("as-text"),
+                )
+                .arg(
+                    arg!(--"emit-key" <KEYFILE> "Write the salt and nonce to KEYFILE and keep only the ciphertext in the chunk")
+                        .requires("password"),
+                )
+                .arg(
+                    arg!(--marker "Prefix the data with a byte per codec so decode --detect-marker can undo them")
+                        .conflicts_with("as-text"),
+                )
+                .arg(
+                    arg!(--base64 "Decode MESSAGE from base64 and store the bytes, for binary payloads")
+                        .conflicts_with("as-text"),
+                )
+                .arg(
+                    arg!(--hex "Decode MESSAGE from hex digits, ignoring whitespace, and store the bytes")
+                        .conflicts_with_all(["as-text", "base64"]),
+                )
+                .arg(arg!(--idempotent "Do nothing if the same message is already stored"))
+                .arg(
+                    arg!(--"replace-or-append" "Replace the first chunk of CHUNK_TYPE, or add one before IEND")
+                        .conflicts_with("idempotent"),
+                )
+                .arg(
+                    arg!(--at <N> "Insert the chunk at index N instead of appending it")
+                        .value_parser(value_parser!(usize))
+                        .conflicts_with_all(["idempotent", "replace-or-append"]),
+                )
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("create")
+                .about("Create a PNG file of a single solid color")
+                .arg(arg!(<OUT> "The PNG file to write"))
+                .arg(
+                    arg!(--width <W> "Image width in pixels")
+                        .value_parser(value_parser!(u32))
+                        .required(true),
+                )
+                .arg(
+                    arg!(--height <H> "Image height in pixels")
+                        .value_parser(value_parser!(u32))
+                        .required(true),
+                )
+                .arg(arg!(--color <RRGGBB> "The fill color as hex").default_value("000000"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("encode-batch")
+                .about("Encode each message of a CSV file into its own copy of a PNG file")
+                .arg(arg!(--template <PATH> "The PNG file to copy for each message").required(true))
+                .arg(arg!(--csv <PATH> "CSV file with rows of message[,filename]").required(true))
+                .arg(arg!(--"chunk-type" <CHUNK_TYPE> "The 4 byte chunk type code").required(true))
+                .arg(arg!(--"out-dir" <DIR> "Directory to write the PNG files to").required(true))
+                .arg(arg!(--"progress-json" "Write a JSON line to stderr for each file written"))
                 .arg_required_else_help(true),
         )
         .subcommand(
             Command::new("decode")
                 .about("Decode secret message in PNG file")
-                .arg(arg!(<PATH> "The PNG file to encode"))
+                .arg(arg!(<PATH> "The PNG file to decode, or - to read from stdin"))
+                .arg(arg!([CHUNK_TYPE]... "The 4 byte chunk type codes to decode"))
+                .arg(chunk_type_bytes_arg())
+                .arg(arg!(--raw "Write the chunk data to stdout exactly as stored"))
+                .arg(
+                    arg!(--"output-file" <FILE> "Write the decoded message bytes to FILE instead of printing them")
+                        .conflicts_with_all(["raw", "stream-output"]),
+                )
+                .arg(arg!(--escape "Escape control characters in the message"))
+                .arg(arg!(--base64 "Print the message as base64, for binary data").conflicts_with("escape"))
+                .arg(
+                    arg!(--hex "Print the message as lowercase hex, for binary data")
+                        .conflicts_with_all(["escape", "base64"]),
+                )
+                .arg(
+                    arg!(--wrap <WIDTH> "Wrap the message at WIDTH characters")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(arg!(--"ignore-bits" "Match chunk types regardless of their property bits"))
+                .arg(codec_arg("Undo CODEC on the chunk data, last given first"))
+                .arg(arg!(--decompress "Inflate messages stored with encode --compress"))
+                .arg(
+                    arg!(--"stream-output" "Write the first matching chunk's data to stdout as soon as it is found, reading no further")
+                        .conflicts_with_all(["offset", "length"]),
+                )
+                .arg(arg!(--password <PASS> "Decrypt messages stored with encode --password"))
+                .arg(
+                    arg!(--"key-file" <KEYFILE> "Decrypt with the salt and nonce from a keyfile written by encode --emit-key")
+                        .requires("password"),
+                )
+                .arg(
+                    arg!(--named <KEY> "Show only the message packed under KEY by encode --named")
+                        .conflicts_with("stream-output"),
+                )
+                .arg(arg!(--"detect-marker" "Undo the codecs named by the marker bytes written by encode --marker"))
+                .args(range_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("has")
+                .about("Exit with 0 if PNG file has a chunk of CHUNK_TYPE and 1 if not")
+                .arg(arg!(<PATH> "The PNG file to check"))
                 .arg(arg!(<CHUNK_TYPE> "The 4 byte chunk type code"))
                 .arg_required_else_help(true),
         )
         .subcommand(
             Command::new("remove")
                 .about("Remove secret message in PNG file")
-                .arg(arg!(<PATH> "The PNG file to encode"))
-                .arg(arg!(<CHUNK_TYPE> "The 4 byte chunk type code"))
+                .arg(arg!(<PATH> "The PNG file to modify, or - to read from stdin and write to stdout"))
+                .arg(arg!([CHUNK_TYPE] "The 4 byte chunk type code; '@ancillary' or '*' strips every ancillary chunk and '@critical' keeps only the critical ones"))
+                .arg(chunk_type_bytes_arg())
+                .arg(arg!(--"ignore-bits" "Match chunk types regardless of their property bits"))
+                .arg(arg!(--all "Remove every chunk of CHUNK_TYPE, not just the first"))
+                .arg(output_arg())
                 .arg_required_else_help(true),
         )
         .subcommand(
             Command::new("print")
                 .about("Print secret message in PNG file")
-                .arg(arg!(<PATH> "The PNG file to encode"))
+                .arg(arg!(<PATH> "The PNG file to print, or - to read from stdin"))
+                .arg(arg!(--"crc-hex" "Show chunk CRCs in hex"))
+                .arg(arg!(--escape "Show each chunk's data as text with control characters escaped"))
+                .arg(arg!(--csv "Print one CSV row of metadata per chunk instead").conflicts_with("format"))
+                .arg(format_arg())
+                .args(range_args())
                 .arg_required_else_help(true),
         )
+        .subcommand(
+            Command::new("move-chunk")
+                .about("Move a chunk to a new position in PNG file")
+                .arg(arg!(<PATH> "The PNG file to modify"))
+                .arg(
+                    arg!(<FROM> "The index of the chunk to move")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(arg!(<TO> "The index to move the chunk to").value_parser(value_parser!(usize)))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("strip-before-idat")
+                .about("Remove ancillary chunks that come before the image data in PNG file")
+                .arg(arg!(<PATH> "The PNG file to modify"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("split")
+                .about("Write each PNG file of several joined end to end to its own file")
+                .arg(arg!(<PATH> "The file holding the concatenated PNG files"))
+                .arg(arg!(<OUT_DIR> "Directory to write the PNG files to"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("minify")
+                .about("Remove every ancillary chunk from PNG file, keeping only the image")
+                .arg(arg!(<PATH> "The PNG file to modify"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("dedup")
+                .about("Remove duplicate chunks from PNG file")
+                .arg(arg!(<PATH> "The PNG file to modify"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("pipe")
+                .about("Apply several operations to PNG file, writing it once at the end")
+                .arg(arg!(<PATH> "The PNG file to modify"))
+                .arg(
+                    arg!(--do <OP> "Operation to apply: strip, encode:TYPE:MESSAGE, remove:TYPE or set-dpi:DPI")
+                        .action(ArgAction::Append)
+                        .required(true),
+                )
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Print image information from the PNG file header")
+                .arg(arg!(<PATH> "The PNG file to inspect"))
+                .arg(format_arg())
+                .args(range_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("profile")
+                .about("Show how many data bytes each chunk type takes up in PNG file")
+                .arg(arg!(<PATH> "The PNG file to inspect"))
+                .args(range_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("count")
+                .about("Count the chunks of each type in PNG file")
+                .arg(arg!(<PATH> "The PNG file to inspect, or - to read from stdin"))
+                .arg(arg!([CHUNK_TYPE] "Only count chunks of this type"))
+                .args(range_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("dump-signature")
+                .about("Print the first 8 bytes of a file and check them against the PNG signature")
+                .arg(arg!(<PATH> "The file to inspect"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("repair-lengths")
+                .about("Correct chunk lengths in PNG file that disagree with the chunks' CRCs")
+                .arg(arg!(<PATH> "The PNG file to repair, or - to read from stdin and write to stdout"))
+                .arg(output_arg())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("verify-file")
+                .about("Check that PNG file has the same chunks as a reference PNG, ignoring volatile ones if asked")
+                .arg(arg!(<PATH> "The PNG file to check, or - to read from stdin"))
+                .arg(arg!(--against <REFERENCE> "The known-good PNG file to compare with").required(true))
+                .arg(arg!(--ignore <CHUNK_TYPE> "Skip ancillary chunks of CHUNK_TYPE in both files").action(ArgAction::Append))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Report chunks in PNG file whose CRC doesn't match their data")
+                .arg(arg!(<PATH> "The PNG file to check, or - to read from stdin"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List every chunk in PNG file with its type, size and CRC")
+                .arg(arg!(<PATH> "The PNG file to inspect"))
+                .arg(arg!(--csv "List the chunks as CSV rows with their property bits").conflicts_with("format"))
+                .arg(format_arg())
+                .args(range_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("browse")
+                .about("Step through the chunks in PNG file in a terminal view with a hex preview of each")
+                .arg(arg!(<PATH> "The PNG file to browse, or - to read from stdin"))
+                .args(range_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(Command::new("chunk-types").about("List the standard PNG chunk types"))
+        .subcommand(
+            Command::new("lint")
+                .about("Check the chunk layout of a PNG file")
+                .visible_alias("validate")
+                .arg(arg!(<PATH> "The PNG file to check"))
+                .arg(arg!(--json "Print findings as JSON"))
+                .args(range_args())
+                .arg_required_else_help(true),
+        )
+}
+
+/// `--chunk-type-bytes`, an alternative to a positional CHUNK_TYPE for scripts.
+fn chunk_type_bytes_arg() -> Arg {
+    arg!(--"chunk-type-bytes" <BYTES> "The chunk type as comma-separated decimal bytes, e.g. 82,117,83,116")
+        .conflicts_with("CHUNK_TYPE")
+        .required_unless_present("CHUNK_TYPE")
+}
+
+fn codec_arg(help: &'static str) -> Arg {
+    arg!(--codec <CODEC>)
+        .help(help)
+        .value_parser(["identity", "base64", "deflate"])
+        .action(ArgAction::Append)
+}
+
+fn output_arg() -> Arg {
+    arg!(-o --output <PATH> "Write the modified PNG here instead of overwriting the input")
+}
+
+/// `--format`, choosing between the human-readable output and JSON for scripts.
+fn format_arg() -> Arg {
+    arg!(--format <FORMAT> "Print as human-readable text or as JSON")
+        .value_parser(["text", "json"])
+        .default_value("text")
+}
+
+/// `--offset`/`--length` for read commands, to parse a PNG embedded in a larger file,
+/// and `--scan` to find it past leading junk.
+fn range_args() -> [Arg; 3] {
+    [
+        arg!(--offset <N> "Start reading the PNG at byte N of the file")
+            .value_parser(value_parser!(usize)),
+        arg!(--length <M> "Read only M bytes of the file, starting at the offset")
+            .value_parser(value_parser!(usize)),
+        arg!(--scan [N] "Start at the PNG signature, skipping up to N (default 1024) leading junk bytes")
+            .value_parser(value_parser!(usize))
+            .default_missing_value("1024")
+            .conflicts_with("offset"),
+    ]
 }
@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::Result;
+use crate::{csv, json};
+
+/// Embed each row's message into a copy of `template`, writing one file per row
+/// into `out_dir`. Rows are `message[,filename]`; rows without a filename are
+/// named `<stem>-<row>.png`. A leading `message[,filename]` header is skipped.
+/// Each file is written by `write`. When `progress` is given, one JSON line is
+/// written to it per file.
+pub fn encode_batch(
+    template: &[u8],
+    csv: &str,
+    chunk_type: &ChunkType,
+    out_dir: &Path,
+    stem: &str,
+    write: &mut dyn FnMut(&Path, &mut Png) -> Result<()>,
+    mut progress: Option<&mut dyn Write>,
+) -> Result<Vec<PathBuf>> {
+    // Parse once up front so a bad template fails before anything is written.
+    Png::try_from(template)?;
+
+    let mut rows = csv::parse(csv)?;
+    if rows
+        .first()
+        .is_some_and(|r| r.first().map(|f| f.as_str()) == Some("message"))
+    {
+        rows.remove(0);
+    }
+
+    // Likewise check every filename before writing, so one bad row writes nothing.
+    let filenames = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| match row.get(1).filter(|f| !f.is_empty()) {
+            Some(f) => check_filename(f).map(|_| f.clone()),
+            None => Ok(format!("{}-{}.png", stem, i + 1)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut seen = HashSet::new();
+    if let Some(name) = filenames.iter().find(|name| !seen.insert(name.as_str())) {
+        return Err(format!("csv filename '{}' is used by more than one row", name).into());
+    }
+
+    fs::create_dir_all(out_dir)?;
+
+    let mut written = Vec::new();
+    for (row, filename) in rows.iter().zip(filenames) {
+        let message = &row[0];
+
+        let mut png = Png::try_from(template)?;
+        png.append_chunk(Chunk::new(*chunk_type, message.as_bytes().to_vec()));
+
+        let path = out_dir.join(filename);
+        write(&path, &mut png)?;
+        if let Some(out) = progress.as_mut() {
+            writeln!(out, "{}", progress_json(&path, png.len_on_disk()))?;
+        }
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Refuse a CSV filename that could land outside the output directory: it must be
+/// a single normal path component. Backslashes are refused on every platform so a
+/// CSV behaves the same wherever it is run.
+fn check_filename(name: &str) -> Result<()> {
+    let mut components = Path::new(name).components();
+    let plain = match (components.next(), components.next()) {
+        (Some(Component::Normal(part)), None) => part == name && !name.contains('\\'),
+        _ => false,
+    };
+    if !plain {
+        return Err(format!(
+            "csv filename '{}' must be a plain file name, not '.', '..' or a path",
+            name
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// A progress line for one written file, e.g. `{"file":"a.png","status":"ok","bytes":123}`.
+fn progress_json(path: &Path, bytes: usize) -> String {
+    format!(
+        "{{\"file\":{},\"status\":\"ok\",\"bytes\":{}}}",
+        json::quote(&path.display().to_string()),
+        bytes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn write(path: &Path, png: &mut Png) -> Result<()> {
+        Ok(fs::write(path, png.as_bytes())?)
+    }
+
+    fn template() -> Vec<u8> {
+        let chunk = Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]);
+        Png::from_chunks(vec![chunk]).as_bytes()
+    }
+
+    #[test]
+    fn test_encode_batch() {
+        let out_dir = std::env::temp_dir().join(format!("pngme-batch-{}", std::process::id()));
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let csv = "message,filename\nfirst secret,one.png\nsecond secret,\n";
+
+        let written = encode_batch(
+            &template(),
+            csv,
+            &chunk_type,
+            &out_dir,
+            "base",
+            &mut write,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            written,
+            vec![out_dir.join("one.png"), out_dir.join("base-2.png")]
+        );
+
+        for (path, message) in written.iter().zip(["first secret", "second secret"]) {
+            let png = Png::try_from(fs::read(path).unwrap().as_slice()).unwrap();
+            let chunk = png.chunk_by_type("ruSt").unwrap();
+            assert_eq!(chunk.data_as_string().unwrap(), message);
+        }
+
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_encode_batch_rejects_unsafe_filenames() {
+        let out_dir = std::env::temp_dir().join(format!("pngme-unsafe-{}", std::process::id()));
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+
+        for name in [
+            "../x.png",
+            "/tmp/x.png",
+            "sub/x.png",
+            "sub\\x.png",
+            "..",
+            ".",
+            "x.png/",
+        ] {
+            let csv = format!("fine,ok.png\nescape,{}\n", name);
+            let err = encode_batch(
+                &template(),
+                &csv,
+                &chunk_type,
+                &out_dir,
+                "base",
+                &mut write,
+                None,
+            )
+            .unwrap_err();
+            assert!(err.to_string().contains(name), "{}", err);
+        }
+        assert!(!out_dir.exists());
+
+        // Two rows naming the same file, directly or via the default name.
+        for csv in ["one,x.png\ntwo,x.png\n", "one,\ntwo,base-1.png\n"] {
+            let err = encode_batch(
+                &template(),
+                csv,
+                &chunk_type,
+                &out_dir,
+                "base",
+                &mut write,
+                None,
+            )
+            .unwrap_err();
+            assert!(err.to_string().contains("more than one row"), "{}", err);
+        }
+        assert!(!out_dir.exists());
+    }
+
+    #[test]
+    fn test_encode_batch_accepts_dotted_filenames() {
+        let out_dir = std::env::temp_dir().join(format!("pngme-dotted-{}", std::process::id()));
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let written = encode_batch(
+            &template(),
+            "one,a..b.png\ntwo,.hidden.png\n",
+            &chunk_type,
+            &out_dir,
+            "base",
+            &mut write,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            written,
+            vec![out_dir.join("a..b.png"), out_dir.join(".hidden.png")]
+        );
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_encode_batch_progress_json() {
+        let out_dir = std::env::temp_dir().join(format!("pngme-progress-{}", std::process::id()));
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let csv = "one,a.png\ntwo,b.png\n";
+
+        let mut progress = Vec::new();
+        encode_batch(
+            &template(),
+            csv,
+            &chunk_type,
+            &out_dir,
+            "base",
+            &mut write,
+            Some(&mut progress),
+        )
+        .unwrap();
+
+        let lines: Vec<String> = String::from_utf8(progress)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        // 8 byte header + IHDR (12 + 13) + message chunk (12 + 3)
+        let expected: Vec<String> = ["a.png", "b.png"]
+            .iter()
+            .map(|name| {
+                format!(
+                    "{{\"file\":{},\"status\":\"ok\",\"bytes\":48}}",
+                    json::quote(&out_dir.join(name).display().to_string())
+                )
+            })
+            .collect();
+        assert_eq!(lines, expected);
+
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
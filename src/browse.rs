@@ -0,0 +1,294 @@
+use crate::png::Png;
+use crate::{hex, Result};
+
+/// Bytes of chunk data shown in the hex preview.
+const PREVIEW_LEN: usize = 64;
+const PREVIEW_WIDTH: usize = 16;
+
+/// The keys the browser responds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Quit,
+}
+
+/// Read-only browsing state over the chunks of a PNG: which chunk is selected,
+/// and how the list and the selected chunk's details are drawn.
+pub struct Browser<'a> {
+    png: &'a Png,
+    selected: usize,
+}
+
+impl<'a> Browser<'a> {
+    pub fn new(png: &'a Png) -> Browser<'a> {
+        Browser { png, selected: 0 }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Move the selection for `key`, returning false once the user asks to quit.
+    pub fn handle(&mut self, key: Key, page: usize) -> bool {
+        let last = self.png.chunks().len().saturating_sub(1);
+        self.selected = match key {
+            Key::Up => self.selected.saturating_sub(1),
+            Key::Down => (self.selected + 1).min(last),
+            Key::PageUp => self.selected.saturating_sub(page.max(1)),
+            Key::PageDown => (self.selected + page.max(1)).min(last),
+            Key::Home => 0,
+            Key::End => last,
+            Key::Quit => return false,
+        };
+        true
+    }
+
+    /// Rows of the chunk list that fit in `rows` lines, scrolled to keep the
+    /// selection in view and marked with `>`.
+    pub fn render_list(&self, rows: usize) -> Vec<String> {
+        let chunks = self.png.chunks();
+        let top = (self.selected + 1).saturating_sub(rows.max(1));
+        chunks
+            .iter()
+            .enumerate()
+            .skip(top)
+            .take(rows)
+            .map(|(i, chunk)| {
+                format!(
+                    "{} {:>3}  {}  {:>10} bytes",
+                    if i == self.selected { '>' } else { ' ' },
+                    i,
+                    chunk.chunk_type(),
+                    chunk.length()
+                )
+            })
+            .collect()
+    }
+
+    /// The selected chunk's type, properties, CRC and a hex preview of its data.
+    pub fn render_details(&self) -> Vec<String> {
+        let Some(chunk) = self.png.chunks().get(self.selected) else {
+            return vec![String::from("no chunks")];
+        };
+        let chunk_type = chunk.chunk_type();
+        let mut lines = vec![
+            format!(
+                "{}  {}",
+                chunk_type,
+                chunk_type
+                    .description()
+                    .unwrap_or("private or unknown chunk type")
+            ),
+            format!(
+                "{}, {}, {}",
+                if chunk_type.is_critical() {
+                    "critical"
+                } else {
+                    "ancillary"
+                },
+                if chunk_type.is_public() {
+                    "public"
+                } else {
+                    "private"
+                },
+                if chunk_type.is_safe_to_copy() {
+                    "safe to copy"
+                } else {
+                    "unsafe to copy"
+                }
+            ),
+            format!(
+                "{} bytes, crc {:08x} ({})",
+                chunk.length(),
+                chunk.crc(),
+                if chunk.is_crc_valid() {
+                    "valid"
+                } else {
+                    "mismatch"
+                }
+            ),
+        ];
+        let data = chunk.data();
+        for (row, bytes) in data[..data.len().min(PREVIEW_LEN)]
+            .chunks(PREVIEW_WIDTH)
+            .enumerate()
+        {
+            let text: String = bytes
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            lines.push(format!(
+                "{:04x}  {:<32}  {}",
+                row * PREVIEW_WIDTH,
+                hex::encode(bytes),
+                text
+            ));
+        }
+        if data.len() > PREVIEW_LEN {
+            lines.push(format!("... {} more bytes", data.len() - PREVIEW_LEN));
+        }
+        lines
+    }
+
+    /// The whole screen for a terminal `rows` lines tall: the list, a rule, then the details.
+    pub fn render(&self, rows: usize) -> Vec<String> {
+        let details = self.render_details();
+        let list_rows = rows.saturating_sub(details.len() + 2).max(1);
+        let mut lines = self.render_list(list_rows);
+        lines.push("-".repeat(40));
+        lines.extend(details);
+        lines.push(String::from(
+            "up/down, pgup/pgdn, home/end to move; q to quit",
+        ));
+        lines
+    }
+}
+
+/// Browse `png` in the terminal until the user quits.
+#[cfg(feature = "tui")]
+pub fn run(png: &Png) -> Result<()> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+    use crossterm::{cursor, execute, terminal};
+    use std::io::Write;
+
+    let mut browser = Browser::new(png);
+    let mut out = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let result = (|| -> Result<()> {
+        loop {
+            let (_, rows) = terminal::size()?;
+            let rows = rows as usize;
+            execute!(
+                out,
+                terminal::Clear(terminal::ClearType::All),
+                cursor::MoveTo(0, 0)
+            )?;
+            write!(out, "{}", browser.render(rows).join("\r\n"))?;
+            out.flush()?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let key = match key.code {
+                KeyCode::Up | KeyCode::Char('k') => Key::Up,
+                KeyCode::Down | KeyCode::Char('j') => Key::Down,
+                KeyCode::PageUp => Key::PageUp,
+                KeyCode::PageDown => Key::PageDown,
+                KeyCode::Home => Key::Home,
+                KeyCode::End => Key::End,
+                KeyCode::Char('q') | KeyCode::Esc => Key::Quit,
+                _ => continue,
+            };
+            if !browser.handle(key, rows / 2) {
+                return Ok(());
+            }
+        }
+    })();
+
+    execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn run(_png: &Png) -> Result<()> {
+    Err(String::from("browse needs pngme to be built with the tui feature").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    fn sample_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::builder()
+                .chunk_type("IHDR")
+                .data(vec![0; 13])
+                .build()
+                .unwrap(),
+            Chunk::builder().data_str("hello, browser").build().unwrap(),
+            Chunk::builder()
+                .chunk_type("IDAT")
+                .data(vec![7; 100])
+                .build()
+                .unwrap(),
+            Chunk::builder().chunk_type("IEND").build().unwrap(),
+        ])
+    }
+
+    #[test]
+    fn test_browser_renders_chunk_list() {
+        let png = sample_png();
+        let browser = Browser::new(&png);
+        assert_eq!(browser.selected(), 0);
+        assert_eq!(
+            browser.render_list(10),
+            vec![
+                ">   0  IHDR          13 bytes",
+                "    1  ruSt          14 bytes",
+                "    2  IDAT         100 bytes",
+                "    3  IEND           0 bytes",
+            ]
+        );
+        let screen = browser.render(20);
+        assert!(screen[0].starts_with(">   0  IHDR"));
+        assert!(screen
+            .iter()
+            .any(|l| l.contains("critical, public, unsafe to copy")));
+    }
+
+    #[test]
+    fn test_browser_navigation() {
+        let png = sample_png();
+        let mut browser = Browser::new(&png);
+        assert!(browser.handle(Key::Up, 2));
+        assert_eq!(browser.selected(), 0);
+        browser.handle(Key::Down, 2);
+        assert_eq!(browser.selected(), 1);
+        let details = browser.render_details();
+        assert!(details[2].ends_with("(valid)"));
+        assert!(details[3].ends_with("hello, browser"));
+
+        browser.handle(Key::PageDown, 2);
+        assert_eq!(browser.selected(), 3);
+        browser.handle(Key::Down, 2);
+        assert_eq!(browser.selected(), 3);
+        browser.handle(Key::Home, 2);
+        assert_eq!(browser.selected(), 0);
+        browser.handle(Key::End, 2);
+        assert_eq!(browser.selected(), 3);
+        assert!(!browser.handle(Key::Quit, 2));
+    }
+
+    #[test]
+    fn test_browser_scrolls_and_previews() {
+        let png = sample_png();
+        let mut browser = Browser::new(&png);
+        browser.handle(Key::End, 2);
+        browser.handle(Key::Up, 2);
+        let list = browser.render_list(2);
+        assert_eq!(list.len(), 2);
+        assert!(list[1].starts_with(">   2  IDAT"));
+
+        let details = browser.render_details();
+        assert_eq!(details.len(), 3 + 4 + 1);
+        assert_eq!(details.last().unwrap(), "... 36 more bytes");
+    }
+}
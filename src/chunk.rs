@@ -1,13 +1,18 @@
 #![allow(dead_code)]
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{self, Read, Write};
+use std::str::FromStr;
 
 use crc::{Crc, CRC_32_ISO_HDLC};
 
 use crate::chunk_type::ChunkType;
+use crate::error::PngError;
 use crate::Result;
 
-#[derive(Debug)]
+/// The largest data length the PNG spec allows a chunk to declare, 2^31-1.
+pub const MAX_LENGTH: u32 = (1 << 31) - 1;
+
+#[derive(Debug, PartialEq, Eq)]
 pub struct Chunk {
     length: u32,
     chunk_type: ChunkType,
@@ -16,19 +21,236 @@ pub struct Chunk {
 }
 
 impl Chunk {
+    /// Start building a chunk; see `ChunkBuilder`.
+    pub fn builder() -> ChunkBuilder {
+        ChunkBuilder::default()
+    }
+
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let crc: Vec<u8> = chunk_type
+        Chunk {
+            length: data.len() as u32,
+            crc: Chunk::compute_crc(&chunk_type, &data),
+            chunk_type,
+            data,
+        }
+    }
+
+    /// Like `try_from`, but accepts a stored CRC that doesn't match the data and
+    /// keeps it, so the chunk serializes back to the same bytes.
+    pub fn try_from_lenient(value: &[u8]) -> std::result::Result<Chunk, PngError> {
+        Chunk::exact(Chunk::parse(value, false)?)
+    }
+
+    /// Like `try_from`, but parses a chunk from the start of `value` and returns
+    /// whatever bytes follow it instead of rejecting them.
+    pub fn try_from_with_remainder(value: &[u8]) -> std::result::Result<(Chunk, &[u8]), PngError> {
+        Chunk::parse(value, true)
+    }
+
+    /// Fail if anything was left over after parsing a chunk.
+    fn exact((chunk, rest): (Chunk, &[u8])) -> std::result::Result<Chunk, PngError> {
+        if !rest.is_empty() {
+            return Err(PngError::TrailingBytes {
+                chunk_type: chunk.chunk_type,
+                extra: rest.len(),
+            });
+        }
+        Ok(chunk)
+    }
+
+    /// Whether the stored CRC matches the one computed from the type and data.
+    pub fn is_crc_valid(&self) -> bool {
+        self.crc == Chunk::compute_crc(&self.chunk_type, &self.data)
+    }
+
+    /// Replace the data, updating the length and CRC to match.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        *self = Chunk::new(self.chunk_type, data);
+    }
+
+    /// Recompute the stored CRC from the type and data.
+    pub fn refresh_crc(&mut self) {
+        self.crc = Chunk::compute_crc(&self.chunk_type, &self.data);
+    }
+
+    fn compute_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+        let bytes: Vec<u8> = chunk_type
             .bytes()
             .iter()
+            .chain(data.iter())
             .cloned()
-            .chain(data.iter().cloned())
             .collect();
-        Chunk {
-            length: data.len() as u32,
+        Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&bytes)
+    }
+
+    /// Parse the chunk at the start of `value`, returning it with the bytes after it.
+    pub(crate) fn parse(
+        value: &[u8],
+        verify_crc: bool,
+    ) -> std::result::Result<(Chunk, &[u8]), PngError> {
+        if value.len() < 12 {
+            return Err(PngError::TooShort {
+                needed: 12,
+                got: value.len(),
+            });
+        }
+        let length = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+        let chunk_type = ChunkType::try_from(&value[4..8])?;
+
+        if length > MAX_LENGTH {
+            return Err(PngError::LengthTooLarge { chunk_type, length });
+        }
+
+        let rest = &value[8..];
+        if rest.len() < length as usize {
+            return Err(PngError::TruncatedChunk {
+                chunk_type,
+                part: "data",
+                needed: length as usize,
+                got: rest.len(),
+            });
+        }
+        let (data, rest) = rest.split_at(length as usize);
+        if rest.len() < 4 {
+            return Err(PngError::TruncatedChunk {
+                chunk_type,
+                part: "crc",
+                needed: 4,
+                got: rest.len(),
+            });
+        }
+        let (crc, rest) = rest.split_at(4);
+        let crc = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+
+        let expected_crc = Chunk::compute_crc(&chunk_type, data);
+        if verify_crc && crc != expected_crc {
+            return Err(PngError::InvalidCrc {
+                expected: expected_crc,
+                actual: crc,
+            });
+        }
+
+        let chunk = Chunk {
+            length,
             chunk_type,
-            data,
-            crc: Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&crc),
+            data: data.to_vec(),
+            crc,
+        };
+        Ok((chunk, rest))
+    }
+
+    /// Like `new`, but parses the type from a string and says which chunk was
+    /// being built if it isn't a valid type.
+    pub fn new_with_type_str(type_str: &str, data: Vec<u8>) -> Result<Chunk> {
+        let chunk_type = ChunkType::from_str(type_str).map_err(|e| {
+            format!(
+                "can't build a {} byte chunk with type '{}': {}",
+                data.len(),
+                type_str,
+                e
+            )
+        })?;
+        Ok(Chunk::new(chunk_type, data))
+    }
+
+    /// Pack named messages into chunk data: a big-endian `u32` count, then for each
+    /// message its name and its bytes, both preceded by a big-endian `u32` length.
+    pub fn pack_messages(messages: &[(String, Vec<u8>)]) -> Vec<u8> {
+        let mut data = (messages.len() as u32).to_be_bytes().to_vec();
+        for (name, message) in messages {
+            for field in [name.as_bytes(), message] {
+                data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+                data.extend_from_slice(field);
+            }
+        }
+        data
+    }
+
+    /// Read back data written by `pack_messages`.
+    pub fn unpack_messages(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut rest = data;
+        let count = take_u32(&mut rest)?;
+        let mut messages = Vec::new();
+        for _ in 0..count {
+            let name = String::from_utf8(take_field(&mut rest)?.to_vec())?;
+            messages.push((name, take_field(&mut rest)?.to_vec()));
+        }
+        if !rest.is_empty() {
+            return Err(format!("{} stray bytes after the packed messages", rest.len()).into());
+        }
+        Ok(messages)
+    }
+
+    /// Build a `tEXt` chunk holding `text` under `keyword`. `tEXt` separates the
+    /// two with a null byte, so neither may contain one.
+    pub fn new_text(keyword: &str, text: &str) -> Result<Chunk> {
+        if keyword.is_empty() || keyword.len() > 79 || keyword.contains('\0') {
+            let e: Box<dyn std::error::Error> =
+                String::from("tEXt keyword must be 1-79 bytes with no null bytes").into();
+            return Err(e);
         }
+        if text.contains('\0') {
+            let e: Box<dyn std::error::Error> = String::from(
+                "message contains a null byte, which tEXt uses as its keyword separator; \
+                 use an iTXt or private chunk type instead",
+            )
+            .into();
+            return Err(e);
+        }
+        let data: Vec<u8> = keyword
+            .bytes()
+            .chain(std::iter::once(0))
+            .chain(text.bytes())
+            .collect();
+        Ok(Chunk::new(ChunkType::try_from(*b"tEXt")?, data))
+    }
+
+    /// Magic bytes that start a chunk saved on its own by `to_portable_bytes`.
+    pub const PORTABLE_MAGIC: [u8; 8] = *b"pngmeCHK";
+
+    /// The chunk as a standalone file: `PORTABLE_MAGIC` followed by `as_bytes`.
+    pub fn to_portable_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::PORTABLE_MAGIC.len() + self.len_on_disk());
+        bytes.extend_from_slice(&Self::PORTABLE_MAGIC);
+        bytes.extend_from_slice(&self.as_bytes());
+        bytes
+    }
+
+    /// Read back a chunk saved by `to_portable_bytes`.
+    pub fn from_portable_bytes(bytes: &[u8]) -> Result<Chunk> {
+        let chunk_bytes = bytes
+            .strip_prefix(&Self::PORTABLE_MAGIC[..])
+            .ok_or("not a pngme chunk file: missing the magic header")?;
+        Ok(Chunk::try_from(chunk_bytes)?)
+    }
+
+    /// Read one chunk from `reader`, consuming only that chunk's bytes. Returns
+    /// `None` when the stream ends cleanly before another chunk starts.
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Option<Chunk>> {
+        let mut bytes = Vec::with_capacity(12);
+        reader.by_ref().take(8).read_to_end(&mut bytes)?;
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        if bytes.len() < 8 {
+            let e: Box<dyn std::error::Error> = format!(
+                "chunk truncated: expected 8 length and type bytes, found {}",
+                bytes.len()
+            )
+            .into();
+            return Err(e);
+        }
+        let length = u32::from_be_bytes(bytes[0..4].try_into()?);
+        if length > MAX_LENGTH {
+            // Refused before reading the body, which would otherwise buffer up to 4 GiB.
+            let chunk_type = ChunkType::try_from(&bytes[4..8])?;
+            return Err(PngError::LengthTooLarge { chunk_type, length }.into());
+        }
+        reader
+            .by_ref()
+            .take(length as u64 + 4)
+            .read_to_end(&mut bytes)?;
+        Ok(Some(Chunk::try_from(bytes.as_slice())?))
     }
 
     pub fn length(&self) -> u32 {
@@ -47,6 +269,15 @@ impl Chunk {
         self.crc
     }
 
+    /// Compare type and data only, so a stale stored CRC doesn't make chunks differ.
+    pub fn content_eq(&self, other: &Chunk) -> bool {
+        self.chunk_type == other.chunk_type && self.data == other.data
+    }
+
+    pub fn data_utf8_valid(&self) -> bool {
+        std::str::from_utf8(&self.data).is_ok()
+    }
+
     pub fn data_as_string(&self) -> Result<String> {
         match String::from_utf8(self.data.clone()) {
             Ok(s) => Ok(s),
@@ -54,73 +285,147 @@ impl Chunk {
         }
     }
 
+    /// Size of the chunk when serialized: length, type and CRC fields plus the data.
+    pub fn len_on_disk(&self) -> usize {
+        self.data.len().saturating_add(12)
+    }
+
+    /// The length field as written by `as_bytes`: big-endian, taken from the data.
+    pub fn length_bytes(&self) -> [u8; 4] {
+        (self.data.len() as u32).to_be_bytes()
+    }
+
+    /// The CRC field as written by `as_bytes`: big-endian.
+    pub fn crc_bytes(&self) -> [u8; 4] {
+        self.crc.to_be_bytes()
+    }
+
+    /// The length is taken from the data rather than the stored `length`, so a
+    /// chunk whose two have drifted apart still serializes correctly.
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.length
-            .to_be_bytes()
-            .iter()
-            .cloned()
-            .chain(self.chunk_type.bytes().iter().cloned())
-            .chain(self.data.iter().cloned())
-            .chain(self.crc.to_be_bytes().iter().cloned())
-            .collect()
+        let mut bytes = Vec::with_capacity(self.len_on_disk());
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Write the chunk as `as_bytes` would lay it out, without copying the data.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.length_bytes())?;
+        w.write_all(&self.chunk_type.bytes())?;
+        w.write_all(&self.data)?;
+        w.write_all(&self.crc_bytes())
     }
 }
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = crate::Error;
+/// Builds a `Chunk` step by step. The type defaults to `ruSt` and the data to empty,
+/// and the CRC is computed unless overridden, which makes deliberately corrupt chunks.
+#[derive(Default)]
+pub struct ChunkBuilder {
+    chunk_type: Option<String>,
+    data: Vec<u8>,
+    crc: Option<u32>,
+}
 
-    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
-        if value.len() < 12 {
-            let e: Box<dyn std::error::Error> = String::from("invalid length").into();
-            return Err(e);
-        } else {
-            let mut reader = BufReader::new(value);
-            let mut buffer: [u8; 4] = [0, 0, 0, 0];
-
-            reader.read_exact(&mut buffer)?;
-            let length = u32::from_be_bytes(buffer);
-
-            reader.read_exact(&mut buffer)?;
-            let chunk_type = ChunkType::try_from(buffer)?;
-
-            let mut rest = Vec::new();
-            reader.read_to_end(&mut rest)?;
-            let data: Vec<u8> = rest.drain(..length as usize).collect();
-
-            let mut reader = BufReader::new(&rest[..]);
-            reader.read_exact(&mut buffer)?;
-            let crc = u32::from_be_bytes(buffer);
-
-            let expected_crc: Vec<u8> = chunk_type
-                .bytes()
-                .iter()
-                .cloned()
-                .chain(data.iter().cloned())
-                .collect();
-            let expected_crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&expected_crc);
-
-            if crc != expected_crc {
-                let e: Box<dyn std::error::Error> = String::from("invalid crc").into();
-                return Err(e);
-            }
+impl ChunkBuilder {
+    pub fn chunk_type(mut self, chunk_type: &str) -> ChunkBuilder {
+        self.chunk_type = Some(chunk_type.to_string());
+        self
+    }
 
-            Ok(Chunk {
-                length,
-                chunk_type,
-                data,
-                crc,
-            })
+    pub fn data(mut self, data: Vec<u8>) -> ChunkBuilder {
+        self.data = data;
+        self
+    }
+
+    pub fn data_str(self, data: &str) -> ChunkBuilder {
+        self.data(data.as_bytes().to_vec())
+    }
+
+    /// Store `crc` in place of the computed CRC.
+    pub fn crc_override(mut self, crc: u32) -> ChunkBuilder {
+        self.crc = Some(crc);
+        self
+    }
+
+    /// Fails if the chunk type isn't valid.
+    pub fn build(self) -> Result<Chunk> {
+        let chunk_type = match self.chunk_type {
+            Some(chunk_type) => ChunkType::from_str(&chunk_type)?,
+            None => ChunkType::default(),
+        };
+        let mut chunk = Chunk::new(chunk_type, self.data);
+        if let Some(crc) = self.crc {
+            chunk.crc = crc;
         }
+        Ok(chunk)
+    }
+}
+
+/// Split `len` bytes off the front of `rest`.
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if rest.len() < len {
+        return Err(format!(
+            "packed messages end early: needed {} more bytes, found {}",
+            len,
+            rest.len()
+        )
+        .into());
     }
+    let (field, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(field)
 }
 
+fn take_u32(rest: &mut &[u8]) -> Result<u32> {
+    let bytes = take(rest, 4)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// A field preceded by its `u32` length.
+fn take_field<'a>(rest: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = take_u32(rest)? as usize;
+    take(rest, len)
+}
+
+/// Render bytes as text safe for a terminal: control characters are escaped like
+/// Rust's `{:?}` does, and bytes that aren't valid UTF-8 become `\xNN`.
+pub fn escape_bytes(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len());
+    for chunk in data.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' | '\'' => out.push(c),
+                c => out.extend(c.escape_debug()),
+            }
+        }
+        for b in chunk.invalid() {
+            out.push_str(&format!("\\x{:02x}", b));
+        }
+    }
+    out
+}
+
+impl TryFrom<&[u8]> for Chunk {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        Chunk::exact(Chunk::parse(value, true)?)
+    }
+}
+
+/// The alternate form (`{:#}`) renders the CRC in hex instead of decimal.
 impl Display for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Chunk {{",)?;
         writeln!(f, "   Length: {}", self.length())?;
         writeln!(f, "   Type: {}", self.chunk_type())?;
         writeln!(f, "   Data: {} bytes", self.data().len())?;
-        writeln!(f, "   Crc: {}", self.crc())?;
+        if f.alternate() {
+            writeln!(f, "   Crc: 0x{:08X}", self.crc())?;
+        } else {
+            writeln!(f, "   Crc: {}", self.crc())?;
+        }
         writeln!(f, "}}",)?;
         Ok(())
     }
@@ -130,7 +435,6 @@ impl Display for Chunk {
 mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
-    use std::str::FromStr;
 
     fn testing_chunk() -> Chunk {
         let data_length: u32 = 42;
@@ -161,6 +465,34 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_new_with_type_str() {
+        let chunk = Chunk::new_with_type_str("RuSt", b"hi".to_vec()).unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "RuSt");
+
+        let err = Chunk::new_with_type_str("Ru5t", b"hi".to_vec()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "can't build a 2 byte chunk with type 'Ru5t': Invalid ChunkType"
+        );
+    }
+
+    #[test]
+    fn test_new_text_chunk() {
+        let chunk = Chunk::new_text("Comment", "hello").unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Comment\0hello");
+    }
+
+    #[test]
+    fn test_null_byte_rejected_for_text_only() {
+        let err = Chunk::new_text("Comment", "hel\0lo").unwrap_err();
+        assert!(err.to_string().contains("null byte"));
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hel\0lo".to_vec());
+        assert_eq!(chunk.data(), b"hel\0lo");
+    }
+
     #[test]
     fn test_chunk_length() {
         let chunk = testing_chunk();
@@ -181,12 +513,122 @@ mod tests {
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[test]
+    fn test_chunk_data_utf8_valid() {
+        assert!(testing_chunk().data_utf8_valid());
+        let chunk = Chunk::new(*testing_chunk().chunk_type(), vec![b'o', b'k', 0xff, 0xfe]);
+        assert!(!chunk.data_utf8_valid());
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_set_data_and_refresh_crc() {
+        let mut chunk = testing_chunk();
+        chunk.set_data(b"short".to_vec());
+        assert_eq!(chunk.length(), 5);
+        assert!(chunk.is_crc_valid());
+
+        let mut chunk = Chunk::builder()
+            .data_str("x")
+            .crc_override(1)
+            .build()
+            .unwrap();
+        chunk.refresh_crc();
+        assert!(chunk.is_crc_valid());
+    }
+
+    #[test]
+    fn test_length_and_crc_bytes() {
+        let chunk = testing_chunk();
+        let bytes = chunk.as_bytes();
+        assert_eq!(chunk.length_bytes(), bytes[..4]);
+        assert_eq!(chunk.crc_bytes(), bytes[bytes.len() - 4..]);
+        assert_eq!(chunk.length_bytes(), [0, 0, 0, 42]);
+        assert_eq!(u32::from_be_bytes(chunk.crc_bytes()), 2882656334);
+    }
+
+    #[test]
+    fn test_chunk_content_eq_ignores_crc() {
+        let chunk = testing_chunk();
+        let stale = Chunk {
+            crc: chunk.crc() ^ 1,
+            ..testing_chunk()
+        };
+        assert!(chunk.content_eq(&stale));
+        assert_ne!(chunk, stale);
+
+        let other = Chunk::new(*chunk.chunk_type(), b"different".to_vec());
+        assert!(!chunk.content_eq(&other));
+    }
+
+    #[test]
+    fn test_as_bytes_uses_data_length() {
+        let chunk = Chunk {
+            length: 7,
+            ..testing_chunk()
+        };
+        let bytes = chunk.as_bytes();
+        assert_eq!(&bytes[..4], &42u32.to_be_bytes());
+        assert_eq!(bytes, testing_chunk().as_bytes());
+    }
+
+    #[test]
+    fn test_as_bytes_round_trips_large_data() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        let chunk = Chunk::new(ChunkType::from_str("IDAT").unwrap(), data.clone());
+        let bytes = chunk.as_bytes();
+        assert_eq!(bytes.len(), 12 + data.len());
+        assert_eq!(bytes.len(), chunk.len_on_disk());
+        let parsed = Chunk::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(parsed.data(), data.as_slice());
+        assert_eq!(parsed.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_portable_bytes_round_trip() {
+        let chunk = testing_chunk();
+        let bytes = chunk.to_portable_bytes();
+        assert_eq!(&bytes[..8], b"pngmeCHK");
+        assert_eq!(&bytes[8..], chunk.as_bytes().as_slice());
+        assert_eq!(Chunk::from_portable_bytes(&bytes).unwrap(), chunk);
+
+        assert!(Chunk::from_portable_bytes(&chunk.as_bytes()).is_err());
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(Chunk::from_portable_bytes(&extra).is_err());
+    }
+
+    #[test]
+    fn test_try_from_lenient() {
+        let mut bytes = testing_chunk().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+
+        assert!(Chunk::try_from(bytes.as_slice()).is_err());
+        let chunk = Chunk::try_from_lenient(&bytes).unwrap();
+        assert!(!chunk.is_crc_valid());
+        assert_eq!(chunk.as_bytes(), bytes);
+        assert!(testing_chunk().is_crc_valid());
+    }
+
+    #[test]
+    fn test_escape_bytes() {
+        assert_eq!(escape_bytes(b"a\nb\tc\0d"), r"a\nb\tc\0d");
+        assert_eq!(escape_bytes(b"\"caf\xc3\xa9\" \xff"), "\"caf\u{e9}\" \\xff");
+    }
+
+    #[test]
+    fn test_chunk_crc_display() {
+        let chunk = testing_chunk();
+        assert!(format!("{}", chunk).contains("   Crc: 2882656334\n"));
+        assert!(format!("{:#}", chunk).contains("   Crc: 0xABD1D84E\n"));
+    }
+
     #[test]
     fn test_valid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -232,7 +674,86 @@ mod tests {
 
         let chunk = Chunk::try_from(chunk_data.as_ref());
 
-        assert!(chunk.is_err());
+        assert_eq!(
+            chunk.unwrap_err(),
+            PngError::InvalidCrc {
+                expected: 2882656334,
+                actual: 2882656333
+            }
+        );
+    }
+
+    #[test]
+    fn test_chunk_from_reader() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes.extend_from_slice(b"trailing");
+        let mut reader = std::io::Cursor::new(bytes);
+
+        let chunk = Chunk::from_reader(&mut reader).unwrap().unwrap();
+        assert_eq!(chunk, testing_chunk());
+        assert_eq!(reader.position(), 54);
+
+        let mut empty = std::io::empty();
+        assert!(Chunk::from_reader(&mut empty).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_truncated_chunk_from_bytes() {
+        let chunk = testing_chunk();
+        let bytes = chunk.as_bytes();
+
+        let err = Chunk::try_from(&bytes[..30]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk 'RuSt' truncated: expected 42 data bytes, found 22"
+        );
+
+        assert_eq!(
+            Chunk::try_from(&bytes[..5]).unwrap_err(),
+            PngError::TooShort { needed: 12, got: 5 }
+        );
+
+        let err = Chunk::try_from(&bytes[..52]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk 'RuSt' truncated: expected 4 crc bytes, found 2"
+        );
+    }
+
+    #[test]
+    fn test_from_reader_rejects_oversized_length() {
+        let header = [&u32::MAX.to_be_bytes()[..], b"RuSt"].concat();
+        // Would never finish if the body were read first.
+        let mut reader = header.as_slice().chain(io::repeat(0));
+        let err = Chunk::from_reader(&mut reader).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "chunk 'RuSt' declares {} data bytes, more than the PNG limit of {}",
+                u32::MAX,
+                MAX_LENGTH
+            )
+        );
+    }
+
+    #[test]
+    fn test_oversized_length_from_bytes() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(
+            Chunk::try_from(bytes.as_ref()).unwrap_err(),
+            PngError::LengthTooLarge {
+                chunk_type: ChunkType::from_str("RuSt").unwrap(),
+                length: u32::MAX
+            }
+        );
+
+        // Within the spec limit but past the end of the input.
+        bytes[..4].copy_from_slice(&MAX_LENGTH.to_be_bytes());
+        assert!(matches!(
+            Chunk::try_from(bytes.as_ref()),
+            Err(PngError::TruncatedChunk { part: "data", .. })
+        ));
     }
 
     #[test]
@@ -255,4 +776,95 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_builder() {
+        let chunk = Chunk::builder()
+            .chunk_type("RuSt")
+            .data_str("This is where your secret message will be!")
+            .build()
+            .unwrap();
+        assert_eq!(chunk, testing_chunk());
+
+        let chunk = Chunk::builder().data(vec![1, 2, 3]).build().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "ruSt");
+        assert_eq!(chunk.data(), [1, 2, 3]);
+        assert!(chunk.is_crc_valid());
+
+        assert!(Chunk::builder().chunk_type("ru1t").build().is_err());
+    }
+
+    #[test]
+    fn test_builder_crc_override() {
+        let chunk = Chunk::builder()
+            .chunk_type("RuSt")
+            .data_str("corrupt")
+            .crc_override(7)
+            .build()
+            .unwrap();
+        assert_eq!(chunk.crc(), 7);
+        assert!(!chunk.is_crc_valid());
+        assert_eq!(
+            Chunk::try_from(chunk.as_bytes().as_slice()).unwrap_err(),
+            PngError::InvalidCrc {
+                expected: Chunk::builder()
+                    .chunk_type("RuSt")
+                    .data_str("corrupt")
+                    .build()
+                    .unwrap()
+                    .crc(),
+                actual: 7
+            }
+        );
+    }
+
+    #[test]
+    fn test_pack_messages() {
+        let messages = vec![
+            (String::from("to"), b"alice".to_vec()),
+            (String::from("from"), b"bob".to_vec()),
+            (String::from("note"), vec![0, 159, 146, 150]),
+        ];
+        let data = Chunk::pack_messages(&messages);
+        assert_eq!(&data[..4], [0, 0, 0, 3]);
+        let unpacked = Chunk::unpack_messages(&data).unwrap();
+        assert_eq!(unpacked, messages);
+        let from = unpacked.iter().find(|(name, _)| name == "from").unwrap();
+        assert_eq!(from.1, b"bob");
+
+        assert!(Chunk::unpack_messages(&data[..data.len() - 1]).is_err());
+        assert!(Chunk::unpack_messages(&[data.as_slice(), &[0]].concat()).is_err());
+        assert_eq!(
+            Chunk::unpack_messages(&Chunk::pack_messages(&[])).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let chunk = testing_chunk();
+        let exact = chunk.as_bytes();
+        assert_eq!(Chunk::try_from(exact.as_slice()).unwrap(), chunk);
+        let (parsed, rest) = Chunk::try_from_with_remainder(&exact).unwrap();
+        assert_eq!(parsed, chunk);
+        assert!(rest.is_empty());
+
+        let overlong = [exact.as_slice(), b"tail"].concat();
+        let err = Chunk::try_from(overlong.as_slice()).unwrap_err();
+        assert_eq!(
+            err,
+            PngError::TrailingBytes {
+                chunk_type: *chunk.chunk_type(),
+                extra: 4
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "chunk 'RuSt' followed by 4 unexpected bytes"
+        );
+        assert!(Chunk::try_from_lenient(&overlong).is_err());
+        let (parsed, rest) = Chunk::try_from_with_remainder(&overlong).unwrap();
+        assert_eq!(parsed, chunk);
+        assert_eq!(rest, b"tail");
+    }
 }
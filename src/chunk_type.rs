@@ -1,14 +1,59 @@
 #![allow(dead_code)]
 use std::{fmt::Display, str::FromStr};
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::error::PngError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ChunkType([u8; 4]);
 
+/// Chunk types defined by the PNG specification, with a short description of each.
+pub const KNOWN_TYPES: &[(&str, &str)] = &[
+    ("IHDR", "Image header: dimensions, bit depth and color type"),
+    ("PLTE", "Palette of colors for indexed-color images"),
+    ("IDAT", "Compressed image data"),
+    ("IEND", "Marks the end of the PNG datastream"),
+    ("acTL", "Animation control for animated PNGs"),
+    ("bKGD", "Default background color"),
+    ("cHRM", "Primary chromaticities and white point"),
+    (
+        "cICP",
+        "Coding-independent code points for video signal type",
+    ),
+    ("eXIf", "Exif metadata"),
+    ("fcTL", "Frame control for animated PNGs"),
+    ("fdAT", "Frame data for animated PNGs"),
+    ("gAMA", "Image gamma"),
+    ("hIST", "Approximate usage frequency of each palette entry"),
+    ("iCCP", "Embedded ICC color profile"),
+    ("iTXt", "International (UTF-8) textual data"),
+    ("pHYs", "Physical pixel dimensions"),
+    ("sBIT", "Significant bits per sample"),
+    ("sPLT", "Suggested palette"),
+    ("sRGB", "Image uses the standard RGB color space"),
+    ("tEXt", "Latin-1 textual data"),
+    ("tIME", "Time of last image modification"),
+    ("tRNS", "Simple transparency"),
+    ("zTXt", "Compressed Latin-1 textual data"),
+];
+
 impl ChunkType {
     pub fn bytes(&self) -> [u8; 4] {
         self.0
     }
 
+    /// The type with every property bit cleared, so "RuSt" and "rust" share a base name.
+    pub fn base_name(&self) -> [u8; 4] {
+        self.0.map(|b| b.to_ascii_lowercase())
+    }
+
+    /// The description of a chunk type defined by the PNG specification.
+    pub fn description(&self) -> Option<&'static str> {
+        KNOWN_TYPES
+            .iter()
+            .find(|(name, _)| name.as_bytes() == self.0)
+            .map(|(_, description)| *description)
+    }
+
     pub fn is_valid(&self) -> bool {
         self.is_reserved_bit_valid()
     }
@@ -32,29 +77,97 @@ impl ChunkType {
         self.0[3].is_ascii_lowercase()
         // self.0[3] & 0b00001000 == 0b00001000
     }
+
+    /// Private, reserved-bit-valid and safe to copy: what a hidden message should use.
+    /// Ancillary types are preferred, so decoders that don't know the type skip it.
+    pub fn is_suitable_for_message(&self) -> bool {
+        !self.is_public() && self.is_reserved_bit_valid() && self.is_safe_to_copy()
+    }
+
+    pub fn set_critical(&mut self, critical: bool) {
+        self.set_uppercase(0, critical);
+    }
+
+    pub fn set_public(&mut self, public: bool) {
+        self.set_uppercase(1, public);
+    }
+
+    pub fn set_reserved_bit_valid(&mut self, valid: bool) {
+        self.set_uppercase(2, valid);
+    }
+
+    pub fn set_safe_to_copy(&mut self, safe: bool) {
+        self.set_uppercase(3, !safe);
+    }
+
+    /// Case `base` into a private, safe-to-copy type with a valid reserved bit,
+    /// e.g. "data" becomes "daTa" or, when `critical`, "DaTa".
+    pub fn for_message(base: ChunkType, critical: bool) -> ChunkType {
+        let mut chunk_type = base;
+        chunk_type.set_critical(critical);
+        chunk_type.set_public(false);
+        chunk_type.set_reserved_bit_valid(true);
+        chunk_type.set_safe_to_copy(true);
+        chunk_type
+    }
+
+    /// A private, ancillary, safe-to-copy type made from the first four ASCII letters
+    /// of `label`, filled out from "ruSt", e.g. "my note" becomes "myNo" and "x" "xuSt".
+    pub fn private_from_label(label: &str) -> ChunkType {
+        let mut bytes = *b"rust";
+        for (slot, letter) in bytes
+            .iter_mut()
+            .zip(label.bytes().filter(u8::is_ascii_alphabetic))
+        {
+            *slot = letter;
+        }
+        ChunkType::for_message(ChunkType(bytes), false)
+    }
+
+    fn set_uppercase(&mut self, i: usize, uppercase: bool) {
+        if uppercase {
+            self.0[i].make_ascii_uppercase();
+        } else {
+            self.0[i].make_ascii_lowercase();
+        }
+    }
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
-    type Error = crate::Error;
+    type Error = PngError;
 
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
         if value.iter().all(|c| c.is_ascii_alphabetic()) {
             Ok(ChunkType(value))
         } else {
-            Err("Invalid ChunkType".into())
+            Err(PngError::InvalidChunkType(value))
         }
     }
 }
 
+impl TryFrom<&[u8]> for ChunkType {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let bytes: [u8; 4] = value
+            .try_into()
+            .map_err(|_| PngError::ChunkTypeLength(value.len()))?;
+        ChunkType::try_from(bytes)
+    }
+}
+
 impl FromStr for ChunkType {
-    type Err = crate::Error;
+    type Err = PngError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.as_bytes().iter().all(|c| c.is_ascii_alphabetic()) {
-            Ok(ChunkType(s.as_bytes().try_into()?))
-        } else {
-            Err("Invalid ChunkType".into())
-        }
+        ChunkType::try_from(s.as_bytes())
+    }
+}
+
+/// `ruSt`: an ancillary, private, safe-to-copy type, suitable for messages.
+impl Default for ChunkType {
+    fn default() -> Self {
+        ChunkType(*b"ruSt")
     }
 }
 
@@ -84,6 +197,20 @@ mod tests {
         assert_eq!(expected, actual.bytes());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_slice() {
+        let actual = ChunkType::try_from(&b"RuSt"[..]).unwrap();
+        assert_eq!(actual.bytes(), *b"RuSt");
+
+        let err = ChunkType::try_from(&b"RuS"[..]).unwrap_err();
+        assert_eq!(err.to_string(), "chunk type must be 4 bytes, got 3");
+        assert_eq!(err, PngError::ChunkTypeLength(3));
+        assert_eq!(
+            ChunkType::try_from(&b"Ru1t"[..]).unwrap_err(),
+            PngError::InvalidChunkType(*b"Ru1t")
+        );
+    }
+
     #[test]
     pub fn test_chunk_type_from_str() {
         let expected = ChunkType::try_from([82, 117, 83, 116]).unwrap();
@@ -145,6 +272,16 @@ mod tests {
         assert!(chunk.is_valid());
     }
 
+    #[test]
+    pub fn test_default_chunk_type() {
+        let chunk = ChunkType::default();
+        assert_eq!(chunk.bytes(), *b"ruSt");
+        assert!(chunk.is_valid());
+        assert!(!chunk.is_critical());
+        assert!(!chunk.is_public());
+        assert!(chunk.is_safe_to_copy());
+    }
+
     #[test]
     pub fn test_invalid_chunk_is_valid() {
         let chunk = ChunkType::from_str("Rust").unwrap();
@@ -154,6 +291,75 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_base_name() {
+        let expected = *b"rust";
+        for name in ["RuSt", "rust", "RUST", "rUsT"] {
+            let chunk = ChunkType::from_str(name).unwrap();
+            assert_eq!(chunk.base_name(), expected);
+        }
+        assert_ne!(ChunkType::from_str("RuSx").unwrap().base_name(), expected);
+    }
+
+    #[test]
+    pub fn test_chunk_type_setters() {
+        let mut chunk = ChunkType::from_str("rust").unwrap();
+        chunk.set_critical(true);
+        chunk.set_public(true);
+        chunk.set_reserved_bit_valid(true);
+        chunk.set_safe_to_copy(false);
+        assert_eq!(chunk.to_string(), "RUST");
+        assert!(chunk.is_critical() && chunk.is_public() && !chunk.is_safe_to_copy());
+    }
+
+    #[test]
+    pub fn test_chunk_type_for_message() {
+        let base = ChunkType::from_str("data").unwrap();
+
+        let ancillary = ChunkType::for_message(base, false);
+        assert_eq!(ancillary.to_string(), "daTa");
+        assert!(ancillary.is_suitable_for_message());
+        assert!(!ancillary.is_critical());
+
+        let critical = ChunkType::for_message(base, true);
+        assert_eq!(critical.to_string(), "DaTa");
+        assert!(critical.is_suitable_for_message());
+
+        assert!(!ChunkType::from_str("tEXt")
+            .unwrap()
+            .is_suitable_for_message());
+    }
+
+    #[test]
+    pub fn test_private_from_label() {
+        for (label, expected) in [
+            ("my note", "myNo"),
+            ("x", "xuSt"),
+            ("", "ruSt"),
+            ("42 IDAT", "idAt"),
+        ] {
+            let chunk_type = ChunkType::private_from_label(label);
+            assert_eq!(chunk_type.to_string(), expected);
+            assert!(chunk_type.is_suitable_for_message());
+            assert!(!chunk_type.is_critical());
+        }
+    }
+
+    #[test]
+    pub fn test_chunk_type_description() {
+        let chunk = ChunkType::from_str("IHDR").unwrap();
+        assert!(chunk.description().unwrap().contains("header"));
+        let chunk = ChunkType::from_str("ruSt").unwrap();
+        assert!(chunk.description().is_none());
+    }
+
+    #[test]
+    pub fn test_known_types_are_valid() {
+        for (name, _) in KNOWN_TYPES.iter() {
+            assert!(ChunkType::from_str(name).unwrap().is_valid());
+        }
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
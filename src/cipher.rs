@@ -0,0 +1,217 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use sha2::Sha256;
+
+use crate::codec::{self, DataCodec};
+use crate::{hex, Result};
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// PBKDF2-HMAC-SHA256 rounds used to stretch the passphrase into a key.
+const ROUNDS: u32 = 100_000;
+/// Names the scheme in a keyfile, so a keyfile from another scheme is refused.
+const ALGORITHM: &str = "aes-256-gcm/pbkdf2-hmac-sha256";
+
+/// The salt and nonce for one message, kept in a keyfile instead of the chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyParams {
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+}
+
+impl KeyParams {
+    /// A fresh random salt and nonce.
+    pub fn generate() -> KeyParams {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng).into();
+        KeyParams { salt, nonce }
+    }
+
+    /// The keyfile text: `key=value` lines for the algorithm, rounds, salt and nonce.
+    pub fn to_keyfile(&self) -> String {
+        format!(
+            "algorithm={}\nrounds={}\nsalt={}\nnonce={}\n",
+            ALGORITHM,
+            ROUNDS,
+            hex::encode(&self.salt),
+            hex::encode(&self.nonce)
+        )
+    }
+
+    pub fn from_keyfile(text: &str) -> Result<KeyParams> {
+        let field = |key: &str| -> Result<&str> {
+            text.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .map(str::trim)
+                .ok_or_else(|| format!("keyfile has no {} line", key).into())
+        };
+        let algorithm = field("algorithm")?;
+        let rounds = field("rounds")?;
+        if algorithm != ALGORITHM || rounds != ROUNDS.to_string() {
+            return Err(format!(
+                "keyfile is for {} with {} rounds, expected {} with {}",
+                algorithm, rounds, ALGORITHM, ROUNDS
+            )
+            .into());
+        }
+        let bytes = |key: &str, len: usize| -> Result<Vec<u8>> {
+            let bytes = hex::decode(field(key)?)?;
+            if bytes.len() != len {
+                return Err(
+                    format!("keyfile {} must be {} bytes, got {}", key, len, bytes.len()).into(),
+                );
+            }
+            Ok(bytes)
+        };
+        Ok(KeyParams {
+            salt: bytes("salt", SALT_LEN)?.try_into().expect("length checked"),
+            nonce: bytes("nonce", NONCE_LEN)?
+                .try_into()
+                .expect("length checked"),
+        })
+    }
+}
+
+/// Encrypts with a key derived from a passphrase. The stored data is the random
+/// salt, then the nonce, then the ciphertext with its authentication tag; with
+/// key params, the salt and nonce come from those and only the ciphertext is stored.
+pub struct Cipher {
+    password: String,
+    params: Option<KeyParams>,
+}
+
+impl Cipher {
+    pub fn new(password: &str) -> Cipher {
+        Cipher {
+            password: password.to_string(),
+            params: None,
+        }
+    }
+
+    /// A cipher using `params` rather than storing a salt and nonce with the data.
+    pub fn with_key_params(password: &str, params: KeyParams) -> Cipher {
+        Cipher {
+            password: password.to_string(),
+            params: Some(params),
+        }
+    }
+
+    fn key(&self, salt: &[u8]) -> Key<Aes256Gcm> {
+        let mut key = Key::<Aes256Gcm>::default();
+        pbkdf2::pbkdf2_hmac::<Sha256>(self.password.as_bytes(), salt, ROUNDS, &mut key);
+        key
+    }
+}
+
+impl DataCodec for Cipher {
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        if let Some(params) = &self.params {
+            return Aes256Gcm::new(&self.key(&params.salt))
+                .encrypt(Nonce::from_slice(&params.nonce), data)
+                .expect("AES-GCM can encrypt any message that fits in memory");
+        }
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&self.key(&salt))
+            .encrypt(&nonce, data)
+            .expect("AES-GCM can encrypt any message that fits in memory");
+
+        let mut out = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(&salt);
+        out.extend_from_slice(&nonce);
+        out.extend(ciphertext);
+        out
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if let Some(params) = &self.params {
+            return Aes256Gcm::new(&self.key(&params.salt))
+                .decrypt(Nonce::from_slice(&params.nonce), data)
+                .map_err(|_| String::from("authentication failed").into());
+        }
+        if data.len() < SALT_LEN + NONCE_LEN {
+            return Err(String::from("authentication failed: encrypted data is too short").into());
+        }
+        let (salt, rest) = data.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        Aes256Gcm::new(&self.key(salt))
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| String::from("authentication failed").into())
+    }
+
+    fn marker(&self) -> u8 {
+        codec::MARKER_ENCRYPTED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let cipher = Cipher::new("correct horse");
+        let stored = cipher.encode(b"meet at noon");
+        assert_eq!(stored.len(), SALT_LEN + NONCE_LEN + 12 + 16);
+        assert!(!stored.windows(4).any(|w| w == b"meet"));
+        assert_eq!(cipher.decode(&stored).unwrap(), b"meet at noon");
+
+        // A fresh salt and nonce each time.
+        assert_ne!(cipher.encode(b"meet at noon"), stored);
+    }
+
+    #[test]
+    fn test_rejects_wrong_password_and_tampering() {
+        let stored = Cipher::new("correct horse").encode(b"meet at noon");
+        let err = Cipher::new("battery staple").decode(&stored).unwrap_err();
+        assert_eq!(err.to_string(), "authentication failed");
+
+        let mut tampered = stored.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(Cipher::new("correct horse").decode(&tampered).is_err());
+        assert!(Cipher::new("correct horse").decode(&stored[..20]).is_err());
+    }
+
+    #[test]
+    fn test_key_params_round_trip() {
+        let params = KeyParams::generate();
+        let keyfile = params.to_keyfile();
+        assert!(keyfile.starts_with("algorithm=aes-256-gcm/pbkdf2-hmac-sha256\nrounds=100000\n"));
+        assert_eq!(KeyParams::from_keyfile(&keyfile).unwrap(), params);
+
+        let cipher = Cipher::with_key_params("correct horse", params.clone());
+        let stored = cipher.encode(b"meet at noon");
+        assert_eq!(stored.len(), 12 + 16);
+        let reread =
+            Cipher::with_key_params("correct horse", KeyParams::from_keyfile(&keyfile).unwrap());
+        assert_eq!(reread.decode(&stored).unwrap(), b"meet at noon");
+        assert!(Cipher::new("correct horse").decode(&stored).is_err());
+
+        let other = keyfile.replace("rounds=100000", "rounds=1");
+        assert!(KeyParams::from_keyfile(&other).is_err());
+        let short = keyfile.replace("nonce=", "nonce=00");
+        assert!(KeyParams::from_keyfile(&short).is_err());
+    }
+
+    #[test]
+    fn test_marker_round_trip() {
+        let mut pipeline = codec::Pipeline::default().with_markers(true);
+        pipeline.push(Box::new(Cipher::new("correct horse")));
+        let stored = pipeline.encode(b"meet at noon");
+        assert_eq!(stored[0], codec::MARKER_ENCRYPTED);
+        assert_eq!(pipeline.decode(&stored).unwrap(), b"meet at noon");
+
+        let err = codec::Pipeline::default()
+            .with_markers(true)
+            .decode(&stored)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the data is encrypted and needs a password to decode"
+        );
+    }
+}
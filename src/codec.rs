@@ -0,0 +1,402 @@
+use crate::Result;
+
+/// A reversible transformation applied to chunk data on its way into and out of a PNG.
+pub trait DataCodec {
+    fn encode(&self, data: &[u8]) -> Vec<u8>;
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>>;
+    /// The byte that marks data stored with this codec; see `MARKERS`.
+    fn marker(&self) -> u8;
+}
+
+/// The marker bytes `Pipeline::with_markers` writes ahead of each codec's output:
+///
+/// | byte   | codec        |
+/// |--------|--------------|
+/// | `0xf8` | `identity`   |
+/// | `0xf9` | `base64`     |
+/// | `0xfa` | `deflate`    |
+/// | `0xfb` | `compressed` |
+/// | `0xfc` | `encrypted`  |
+///
+/// None of these can start UTF-8 text, so marked data is told apart from plain messages.
+/// The `identity` marker always comes innermost and ends the chain.
+pub const MARKERS: [(u8, &str); 5] = [
+    (MARKER_IDENTITY, "identity"),
+    (0xf9, "base64"),
+    (0xfa, "deflate"),
+    (0xfb, "compressed"),
+    (MARKER_ENCRYPTED, "encrypted"),
+];
+
+pub const MARKER_IDENTITY: u8 = 0xf8;
+pub const MARKER_ENCRYPTED: u8 = 0xfc;
+
+/// The marker byte for the codec called `name`.
+fn marker_of(name: &str) -> u8 {
+    MARKERS
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(marker, _)| *marker)
+        .expect("every codec has a marker")
+}
+
+/// A built-in codec by its marker byte. Encrypted data needs the password, so its
+/// codec has to come from the pipeline instead.
+fn codec_by_marker(marker: u8) -> Result<Box<dyn DataCodec>> {
+    match MARKERS.iter().find(|(m, _)| *m == marker) {
+        Some((MARKER_ENCRYPTED, _)) => {
+            Err(String::from("the data is encrypted and needs a password to decode").into())
+        }
+        Some((_, name)) => codec_by_name(name),
+        None => Err(format!("unknown codec marker {:#04x}", marker).into()),
+    }
+}
+
+/// Leaves the data as it is.
+pub struct Identity;
+
+impl DataCodec for Identity {
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        data.to_vec()
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+
+    fn marker(&self) -> u8 {
+        MARKER_IDENTITY
+    }
+}
+
+/// Standard base64 with `=` padding.
+pub struct Base64;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl DataCodec for Base64 {
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len().div_ceil(3) * 4);
+        for group in data.chunks(3) {
+            let bytes = [
+                group[0],
+                *group.get(1).unwrap_or(&0),
+                *group.get(2).unwrap_or(&0),
+            ];
+            let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                if i <= group.len() {
+                    out.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize]);
+                } else {
+                    out.push(b'=');
+                }
+            }
+        }
+        out
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if !data.len().is_multiple_of(4) {
+            return Err(format!(
+                "base64 data must be a multiple of 4 long, got {}",
+                data.len()
+            )
+            .into());
+        }
+        let mut out = Vec::with_capacity(data.len() / 4 * 3);
+        for (i, group) in data.chunks(4).enumerate() {
+            let last = (i + 1) * 4 == data.len();
+            let padding = group.iter().rev().take_while(|&&b| b == b'=').count();
+            if padding > 2 || (padding > 0 && !last) {
+                return Err(String::from("misplaced base64 padding").into());
+            }
+            let mut bits = 0u32;
+            for (j, &b) in group[..4 - padding].iter().enumerate() {
+                let value = BASE64_ALPHABET
+                    .iter()
+                    .position(|&c| c == b)
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid base64 character {:?} at offset {}",
+                            b as char,
+                            i * 4 + j
+                        )
+                    })?;
+                bits |= (value as u32) << (18 - 6 * j);
+            }
+            out.extend_from_slice(&bits.to_be_bytes()[1..4 - padding]);
+        }
+        Ok(out)
+    }
+
+    fn marker(&self) -> u8 {
+        marker_of("base64")
+    }
+}
+
+/// zlib-compressed data, as PNG itself uses for `IDAT` and `zTXt`.
+#[cfg(feature = "flate2")]
+pub struct Deflate;
+
+#[cfg(feature = "flate2")]
+impl DataCodec for Deflate {
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(data)
+            .expect("writing to a Vec can't fail");
+        encoder.finish().expect("writing to a Vec can't fail")
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        use std::io::Read;
+
+        let mut out = Vec::new();
+        flate2::read::ZlibDecoder::new(data).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    fn marker(&self) -> u8 {
+        marker_of("deflate")
+    }
+}
+
+/// `Deflate` behind a magic prefix, so decoding can tell compressed data from plain.
+#[cfg(feature = "flate2")]
+pub struct Compressed;
+
+/// Prefix of data stored by `Compressed`; long enough that plain data won't start with it by accident.
+pub const COMPRESSED_MAGIC: &[u8] = b"\x01zlb";
+
+#[cfg(feature = "flate2")]
+impl DataCodec for Compressed {
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = COMPRESSED_MAGIC.to_vec();
+        out.extend(Deflate.encode(data));
+        out
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match data.strip_prefix(COMPRESSED_MAGIC) {
+            Some(rest) => Deflate.decode(rest),
+            None => Ok(data.to_vec()),
+        }
+    }
+
+    fn marker(&self) -> u8 {
+        marker_of("compressed")
+    }
+}
+
+/// The codec called `name`: `identity`, `base64` and, with the `flate2` feature,
+/// `deflate` and `compressed`.
+pub fn codec_by_name(name: &str) -> Result<Box<dyn DataCodec>> {
+    Ok(match name {
+        "identity" => Box::new(Identity),
+        "base64" => Box::new(Base64),
+        #[cfg(feature = "flate2")]
+        "deflate" => Box::new(Deflate),
+        #[cfg(feature = "flate2")]
+        "compressed" => Box::new(Compressed),
+        #[cfg(not(feature = "flate2"))]
+        "deflate" | "compressed" => {
+            return Err(format!(
+                "the {} codec needs pngme to be built with the flate2 feature",
+                name
+            )
+            .into())
+        }
+        _ => return Err(format!("unknown codec '{}'", name).into()),
+    })
+}
+
+/// Codecs applied one after another: encoding runs them in order, decoding in reverse.
+#[derive(Default)]
+pub struct Pipeline {
+    codecs: Vec<Box<dyn DataCodec>>,
+    markers: bool,
+}
+
+impl Pipeline {
+    /// Build a pipeline from codec names; see `codec_by_name`.
+    pub fn from_names<'a, I: IntoIterator<Item = &'a str>>(names: I) -> Result<Pipeline> {
+        let mut pipeline = Pipeline::default();
+        for name in names {
+            pipeline.push(codec_by_name(name)?);
+        }
+        Ok(pipeline)
+    }
+
+    /// Add `codec` as the last step of the pipeline.
+    pub fn push(&mut self, codec: Box<dyn DataCodec>) {
+        self.codecs.push(codec);
+    }
+
+    /// Whether to prefix each codec's output with its marker byte when encoding, and to
+    /// read the markers back when decoding. Decoding data without a leading marker falls
+    /// back to running the pipeline's own codecs.
+    pub fn with_markers(mut self, markers: bool) -> Pipeline {
+        self.markers = markers;
+        self
+    }
+
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        if !self.markers {
+            return self
+                .codecs
+                .iter()
+                .fold(data.to_vec(), |data, codec| codec.encode(&data));
+        }
+        let start = [&[MARKER_IDENTITY], data].concat();
+        self.codecs
+            .iter()
+            .filter(|codec| codec.marker() != MARKER_IDENTITY)
+            .fold(start, |data, codec| {
+                [vec![codec.marker()], codec.encode(&data)].concat()
+            })
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let marked = data
+            .first()
+            .is_some_and(|b| MARKERS.iter().any(|(m, _)| m == b));
+        if !self.markers || !marked {
+            return self
+                .codecs
+                .iter()
+                .rev()
+                .try_fold(data.to_vec(), |data, codec| codec.decode(&data));
+        }
+
+        let mut data = data.to_vec();
+        loop {
+            let Some((&marker, rest)) = data.split_first() else {
+                return Err(String::from("marked data ends before its identity marker").into());
+            };
+            if marker == MARKER_IDENTITY {
+                return Ok(rest.to_vec());
+            }
+            data = match self.codecs.iter().find(|codec| codec.marker() == marker) {
+                Some(codec) => codec.decode(rest)?,
+                None => codec_by_marker(marker)?.decode(rest)?,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(Base64.encode(plain.as_bytes()), encoded.as_bytes());
+            assert_eq!(Base64.decode(encoded.as_bytes()).unwrap(), plain.as_bytes());
+        }
+        assert_eq!(
+            Base64.decode(&Base64.encode(&[0, 255, 128])).unwrap(),
+            vec![0, 255, 128]
+        );
+    }
+
+    #[test]
+    fn test_base64_rejects_bad_input() {
+        assert!(Base64.decode(b"Zm9").is_err());
+        assert!(Base64.decode(b"Z=9v").is_err());
+        assert!(Base64.decode(b"Zg==Zm9v").is_err());
+        assert_eq!(
+            Base64.decode(b"Zm!v").unwrap_err().to_string(),
+            "invalid base64 character '!' at offset 2"
+        );
+    }
+
+    #[test]
+    fn test_pipeline_order() {
+        let pipeline = Pipeline::from_names(["base64", "identity", "base64"]).unwrap();
+        let encoded = pipeline.encode(b"hi");
+        assert_eq!(encoded, Base64.encode(&Base64.encode(b"hi")));
+        assert_eq!(pipeline.decode(&encoded).unwrap(), b"hi");
+
+        assert!(Pipeline::from_names(["rot13"]).is_err());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_deflate_then_base64() {
+        let message = "a message that repeats, a message that repeats, a message that repeats";
+        let pipeline = Pipeline::from_names(["deflate", "base64"]).unwrap();
+        let encoded = pipeline.encode(message.as_bytes());
+        assert!(Base64.decode(&encoded).is_ok());
+        assert!(encoded.len() < message.len());
+        assert_eq!(pipeline.decode(&encoded).unwrap(), message.as_bytes());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_compressed() {
+        let message = "ha".repeat(500);
+        let stored = Compressed.encode(message.as_bytes());
+        assert!(stored.starts_with(COMPRESSED_MAGIC));
+        assert!(stored.len() < message.len());
+        assert_eq!(Compressed.decode(&stored).unwrap(), message.as_bytes());
+
+        // Data stored without the magic passes through, even when it starts with 0x01.
+        assert_eq!(Compressed.decode(b"plain").unwrap(), b"plain");
+        assert_eq!(Compressed.decode(b"\x01plain").unwrap(), b"\x01plain");
+        assert_eq!(Compressed.decode(b"\x01").unwrap(), b"\x01");
+
+        // With the magic present, bad compressed data is an error rather than passed through.
+        assert!(Compressed.decode(b"\x01zlbnot deflate").is_err());
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    #[test]
+    fn test_deflate_needs_feature() {
+        assert!(Pipeline::from_names(["deflate", "base64"]).is_err());
+    }
+
+    #[test]
+    fn test_markers_round_trip() {
+        let mut names = vec!["identity", "base64"];
+        if cfg!(feature = "flate2") {
+            names.extend(["deflate", "compressed"]);
+        }
+        let detect = Pipeline::default().with_markers(true);
+        for name in names {
+            let pipeline = Pipeline::from_names([name]).unwrap().with_markers(true);
+            let stored = pipeline.encode(b"hello marker");
+            assert_eq!(stored[0], codec_by_name(name).unwrap().marker());
+            assert_eq!(detect.decode(&stored).unwrap(), b"hello marker", "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_markers_chain() {
+        let pipeline = Pipeline::from_names(["base64", "base64"])
+            .unwrap()
+            .with_markers(true);
+        let stored = pipeline.encode(b"hi");
+        assert_eq!(stored[0], 0xf9);
+        let detect = Pipeline::default().with_markers(true);
+        assert_eq!(detect.decode(&stored).unwrap(), b"hi");
+
+        // Without markers on either side the data is left to the pipeline's codecs.
+        assert_eq!(detect.decode(b"plain").unwrap(), b"plain");
+        assert_eq!(Pipeline::default().decode(&stored).unwrap(), stored);
+        assert!(detect.decode(&[0xf9]).is_err());
+        assert!(detect.decode(&[0xfe]).is_ok());
+    }
+}
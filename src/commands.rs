@@ -0,0 +1,2987 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::ArgMatches;
+
+use crate::chunk::{escape_bytes, Chunk};
+use crate::chunk_type::{self, ChunkType};
+use crate::codec::{self, DataCodec, Pipeline};
+use crate::png::{ChunkDiff, Header, Png};
+use crate::{batch, browse, create, csv, gzip, hex, json, lint, pipe, PngError, Result};
+
+/// The PATH (or `--output`) that stands for stdin or stdout.
+const STDIO: &str = "-";
+
+/// Append `message` to `png` in a new chunk of `chunk_type`.
+pub fn encode_message(png: &mut Png, chunk_type: &str, message: &str) -> Result<()> {
+    png.append_chunk(message_chunk(chunk_type, message)?);
+    Ok(())
+}
+
+fn message_chunk(chunk_type: &str, message: &str) -> Result<Chunk> {
+    Ok(Chunk::new(
+        parse_chunk_type(chunk_type)?,
+        message.as_bytes().to_vec(),
+    ))
+}
+
+/// Parse a chunk type given on the command line or to the library API, naming
+/// the bad input in the error so every entry point reports it the same way.
+pub fn parse_chunk_type(s: &str) -> Result<ChunkType> {
+    ChunkType::from_str(s).map_err(|e| format!("invalid chunk type '{}': {}", s, e).into())
+}
+
+/// Append `chunk` unless `png` already holds one with the same type and data,
+/// returning whether it was appended.
+pub fn append_if_absent(png: &mut Png, chunk: Chunk) -> bool {
+    if png.chunks().iter().any(|c| c.content_eq(&chunk)) {
+        return false;
+    }
+    png.append_chunk(chunk);
+    true
+}
+
+/// Append `message` to `png` as a `tEXt` chunk under `keyword`.
+pub fn encode_text(png: &mut Png, keyword: &str, message: &str) -> Result<()> {
+    png.append_chunk(Chunk::new_text(keyword, message)?);
+    Ok(())
+}
+
+/// The first chunk of `chunk_type`, or with `ignore_bits` the first whose type
+/// has the same base name regardless of its property bits.
+pub fn find_message<'a>(png: &'a Png, chunk_type: &str, ignore_bits: bool) -> Result<&'a Chunk> {
+    let chunk_type = parse_chunk_type(chunk_type)?;
+    let e: Box<dyn std::error::Error> = String::from("no message found").into();
+    png.chunks()
+        .iter()
+        .find(|c| type_matches(c.chunk_type(), &chunk_type, ignore_bits))
+        .ok_or(e)
+}
+
+/// Like `find_message`, but reads `reader` only as far as the matching chunk.
+pub fn find_message_in_stream<R: Read>(
+    reader: R,
+    chunk_type: &str,
+    ignore_bits: bool,
+) -> Result<Chunk> {
+    let chunk_type = parse_chunk_type(chunk_type)?;
+    let e: Box<dyn std::error::Error> = String::from("no message found").into();
+    Png::find_in_stream(reader, |c| {
+        type_matches(c.chunk_type(), &chunk_type, ignore_bits)
+    })?
+    .ok_or(e)
+}
+
+/// Write the data of the first chunk of `chunk_type` in `input` to `out` as soon
+/// as it is found, undoing `codecs`, without reading any further.
+pub fn stream_message<R: Read, W: Write>(
+    input: R,
+    chunk_type: &str,
+    ignore_bits: bool,
+    codecs: &Pipeline,
+    out: &mut W,
+) -> Result<()> {
+    let chunk = find_message_in_stream(input, chunk_type, ignore_bits)?;
+    out.write_all(&codecs.decode(chunk.data())?)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn type_matches(found: &ChunkType, wanted: &ChunkType, ignore_bits: bool) -> bool {
+    if ignore_bits {
+        found.base_name() == wanted.base_name()
+    } else {
+        found == wanted
+    }
+}
+
+/// The message stored in the first chunk of `chunk_type`.
+pub fn decode_message(png: &Png, chunk_type: &str, ignore_bits: bool) -> Result<String> {
+    find_message(png, chunk_type, ignore_bits)?.data_as_string()
+}
+
+/// The first chunk of each of `chunk_types`, or `None` where there is no such chunk.
+pub fn find_messages<'a>(
+    png: &'a Png,
+    chunk_types: &[&str],
+    ignore_bits: bool,
+) -> Result<Vec<Option<&'a Chunk>>> {
+    chunk_types
+        .iter()
+        .map(|chunk_type| {
+            parse_chunk_type(chunk_type)?;
+            Ok(find_message(png, chunk_type, ignore_bits).ok())
+        })
+        .collect()
+}
+
+/// The message for each of `chunk_types`, or `None` where there is no such chunk.
+pub fn decode_messages(
+    png: &Png,
+    chunk_types: &[&str],
+    ignore_bits: bool,
+) -> Result<Vec<Option<String>>> {
+    find_messages(png, chunk_types, ignore_bits)?
+        .into_iter()
+        .map(|chunk| chunk.map(|c| c.data_as_string()).transpose())
+        .collect()
+}
+
+/// Remove and return the first chunk of `chunk_type`.
+pub fn remove_message(png: &mut Png, chunk_type: &str, ignore_bits: bool) -> Result<Chunk> {
+    let chunk_type = find_message(png, chunk_type, ignore_bits)?
+        .chunk_type()
+        .to_string();
+    png.remove_chunk(&chunk_type)
+}
+
+/// Read the PNG named by the `PATH` argument, sliced by `--offset`/`--length`
+/// for the commands that have them.
+fn read_png(matches: &ArgMatches) -> Result<(PathBuf, Png)> {
+    let path = PathBuf::from_str(matches.get_one::<String>("PATH").expect("required"))?;
+    let contents = read_path(&path)?;
+    let contents = input_range(matches, &contents)?;
+    let png = if matches.get_flag("keep-crc") {
+        Png::try_from_lenient(contents)?
+    } else {
+        Png::try_from(contents)?
+    };
+    warn_if_bloated(&png, matches);
+    Ok((path, png))
+}
+
+/// Fail before anything is read when PATH is a file larger than `--max-file-size`.
+/// Streaming decodes read only as far as they need to, so they are let through.
+pub fn check_file_size(matches: &ArgMatches) -> Result<()> {
+    let Some(path) = matches.try_get_one::<String>("PATH").ok().flatten() else {
+        return Ok(());
+    };
+    if path == STDIO || matches.try_get_one::<bool>("stream-output").ok().flatten() == Some(&true) {
+        return Ok(());
+    }
+    let limit = *matches
+        .get_one::<u64>("max-file-size")
+        .expect("has default");
+    let size = fs::metadata(path)?.len();
+    if size > limit {
+        return Err(format!(
+            "{} is {} bytes, over the --max-file-size limit of {}; raise the limit, or use decode --stream-output to read only as far as the message",
+            path, size, limit
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// The contents of `path`, or of stdin when it is `-`.
+fn read_path(path: &Path) -> Result<Vec<u8>> {
+    if path == Path::new(STDIO) {
+        let mut contents = Vec::new();
+        io::stdin().lock().read_to_end(&mut contents)?;
+        Ok(contents)
+    } else {
+        Ok(fs::read(path)?)
+    }
+}
+
+/// Whether the modified PNG goes to stdout: `--output -`, or no `--output` and a PATH of `-`.
+fn writes_to_stdout(matches: &ArgMatches) -> bool {
+    let output = matches.try_get_one::<String>("output").ok().flatten();
+    output
+        .or_else(|| matches.get_one::<String>("PATH"))
+        .map(String::as_str)
+        == Some(STDIO)
+}
+
+/// Print a status line, on stderr when stdout carries the PNG itself. `--quiet` silences it.
+fn status(matches: &ArgMatches, message: &str) {
+    if matches.get_flag("quiet") {
+        return;
+    }
+    if writes_to_stdout(matches) {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Where a write command puts the PNG: `--output` if given, else PATH, with `-` shown as stdout.
+fn destination(path: &Path, matches: &ArgMatches) -> String {
+    let output = matches.try_get_one::<String>("output").ok().flatten();
+    let path = output.map_or(path, Path::new);
+    if path == Path::new(STDIO) {
+        String::from("stdout")
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// `what` happened, followed by the size and chunk count of the resulting file.
+fn change_summary(what: &str, png: &Png) -> String {
+    format!(
+        "{}; file now {} bytes, {} chunks",
+        what,
+        png.len_on_disk(),
+        png.chunks().len()
+    )
+}
+
+/// Like `read_png`, for commands that only read: gzipped input is decompressed first.
+fn read_input_png(matches: &ArgMatches) -> Result<Png> {
+    let path = matches.get_one::<String>("PATH").expect("required");
+    let contents = gzip::decompress(read_path(Path::new(path))?)?;
+    parse_input(matches, input_range(matches, &contents)?)
+}
+
+/// Parse `contents` as `read_input_png` does, honoring `--keep-crc`.
+fn parse_input(matches: &ArgMatches, contents: &[u8]) -> Result<Png> {
+    let png = if matches.get_flag("keep-crc") {
+        Png::try_from_lenient(contents)?
+    } else {
+        Png::try_from(contents)?
+    };
+    warn_if_bloated(&png, matches);
+    Ok(png)
+}
+
+fn warn_if_bloated(png: &Png, matches: &ArgMatches) {
+    if matches.get_flag("no-warnings") {
+        return;
+    }
+    let limit = *matches
+        .get_one::<usize>("chunk-limit-warn")
+        .expect("defaulted");
+    if let Some(warning) = chunk_limit_warning(png, limit) {
+        eprintln!("{}", warning);
+    }
+}
+
+/// An advisory when `png` has more than `limit` ancillary chunks, as files bloated
+/// by repeated metadata do.
+pub fn chunk_limit_warning(png: &Png, limit: usize) -> Option<String> {
+    let ancillary = png
+        .chunks()
+        .iter()
+        .filter(|c| !c.chunk_type().is_critical())
+        .count();
+    (ancillary > limit).then(|| {
+        format!(
+            "warning: {} ancillary chunks (more than {}); consider `minify` or `dedup`",
+            ancillary, limit
+        )
+    })
+}
+
+fn input_range<'a>(matches: &ArgMatches, contents: &'a [u8]) -> Result<&'a [u8]> {
+    // Write commands don't define the range arguments.
+    let mut offset = matches
+        .try_get_one::<usize>("offset")
+        .ok()
+        .flatten()
+        .copied();
+    let length = matches.try_get_one::<usize>("length").ok().flatten();
+    if let Some(limit) = matches.try_get_one::<usize>("scan").ok().flatten() {
+        let start = Png::signature_offset(contents, *limit)
+            .ok_or_else(|| format!("no PNG signature in the first {} bytes", limit))?;
+        if start > 0 && !matches.get_flag("quiet") {
+            eprintln!("skipped {} leading bytes before the PNG signature", start);
+        }
+        offset = Some(start);
+    }
+    slice_input(contents, offset.unwrap_or(0), length.copied())
+}
+
+/// Cut `[offset..offset + length]` out of `contents`, or everything from `offset`
+/// on when there is no length, checking the range fits.
+pub fn slice_input(contents: &[u8], offset: usize, length: Option<usize>) -> Result<&[u8]> {
+    let end = match length {
+        Some(length) => offset.checked_add(length),
+        None => Some(contents.len()),
+    };
+    match end {
+        Some(end) if offset <= end && end <= contents.len() => Ok(&contents[offset..end]),
+        _ => Err(format!(
+            "range starting at byte {}{} is past the end of the {} byte file",
+            offset,
+            length.map_or(String::new(), |l| format!(" with length {}", l)),
+            contents.len()
+        )
+        .into()),
+    }
+}
+
+pub fn encode(matches: &ArgMatches) -> Result<()> {
+    let (path, mut png) = read_png(matches)?;
+
+    let mut chunk_type = matches
+        .get_one::<String>("CHUNK_TYPE")
+        .expect("required")
+        .clone();
+    if let Some(kind) = matches.get_one::<String>("normalize-type-case") {
+        let base = parse_chunk_type(&chunk_type)?;
+        chunk_type = ChunkType::for_message(base, kind == "critical").to_string();
+    } else if matches.get_flag("as-private") {
+        chunk_type = ChunkType::private_from_label(&chunk_type).to_string();
+        status(matches, &format!("Using chunk type {}", chunk_type));
+    }
+    let chunk_type = chunk_type.as_str();
+    let parsed = parse_chunk_type(chunk_type)?;
+    let chunk = if let Some(data) = matches.get_one::<String>("data-hex") {
+        Chunk::new(parsed, hex::decode(data)?)
+    } else if matches.get_flag("data-stdin-hex") {
+        Chunk::new(parsed, read_hex(&mut io::stdin().lock())?)
+    } else if let Some(file) = matches.get_one::<String>("message-file") {
+        Chunk::new(parsed, fs::read(file)?)
+    } else if let Some(template) = matches.get_one::<String>("template-message") {
+        let filename = path.file_name().map_or(String::from(STDIO), |name| {
+            name.to_string_lossy().into_owned()
+        });
+        let message = fill_template(template, &filename, &today(), &png.ihdr()?);
+        Chunk::new(parsed, message.into_bytes())
+    } else if let Some(pairs) = matches.get_many::<String>("named") {
+        let messages = pairs
+            .map(|pair| match pair.split_once('=') {
+                Some((name, message)) => Ok((name.to_string(), message.as_bytes().to_vec())),
+                None => Err(format!("--named takes KEY=VALUE, got '{}'", pair).into()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Chunk::new(parsed, Chunk::pack_messages(&messages))
+    } else {
+        let message = matches.get_one::<String>("MESSAGE").expect("required");
+        match matches.get_one::<String>("as-text") {
+            Some(keyword) => {
+                if chunk_type != "tEXt" {
+                    let e: Box<dyn std::error::Error> =
+                        String::from("--as-text requires the tEXt chunk type").into();
+                    return Err(e);
+                }
+                Chunk::new_text(keyword, message)?
+            }
+            None if matches.get_flag("hex") => Chunk::new(parsed, hex::decode_spaced(message)?),
+            None if matches.get_flag("base64") => {
+                Chunk::new(parsed, codec::Base64.decode(message.as_bytes())?)
+            }
+            None => message_chunk(chunk_type, message)?,
+        }
+    };
+    let chunk = Chunk::new(*chunk.chunk_type(), codecs(matches)?.encode(chunk.data()));
+    let what = format!(
+        "encoded '{}' ({} bytes) into {}",
+        chunk.chunk_type(),
+        chunk.length(),
+        destination(&path, matches)
+    );
+
+    if matches.get_flag("idempotent") {
+        if !append_if_absent(&mut png, chunk) {
+            status(matches, "unchanged");
+            return Ok(());
+        }
+    } else if matches.get_flag("replace-or-append") {
+        if png.chunk_index_by_type(chunk.chunk_type()).is_some() {
+            png.replace_chunk(chunk)?;
+        } else {
+            png.append_before_iend(chunk);
+        }
+    } else if let Some(index) = matches.get_one::<usize>("at") {
+        png.insert_chunk(*index, chunk)?;
+    } else {
+        png.append_chunk(chunk);
+    }
+
+    write_in_place(&path, &mut png, matches)?;
+    status(matches, &change_summary(&what, &png));
+    Ok(())
+}
+
+pub fn create(matches: &ArgMatches) -> Result<()> {
+    let out = PathBuf::from_str(matches.get_one::<String>("OUT").expect("required"))?;
+    let width = *matches.get_one::<u32>("width").expect("required");
+    let height = *matches.get_one::<u32>("height").expect("required");
+    let color = hex::decode(matches.get_one::<String>("color").expect("defaulted"))?;
+    let rgb: [u8; 3] = color.try_into().map_err(|color: Vec<u8>| {
+        format!(
+            "color must be 3 bytes of hex like ff8800, got {}",
+            color.len()
+        )
+    })?;
+
+    let mut png = create::solid_png(width, height, rgb)?;
+    if out.exists() {
+        write_in_place(&out, &mut png, matches)
+    } else {
+        write_png(&out, &png, matches.get_flag("verify-after-write"))
+    }
+}
+
+/// Read hex-encoded bytes from `input`, ignoring a trailing newline.
+fn read_hex<R: Read>(input: &mut R) -> Result<Vec<u8>> {
+    let mut text = String::new();
+    input.read_to_string(&mut text)?;
+    hex::decode(text.trim_end())
+}
+
+/// `template` with `{filename}`, `{date}`, `{width}` and `{height}` filled in.
+fn fill_template(template: &str, filename: &str, date: &str, header: &Header) -> String {
+    template
+        .replace("{filename}", filename)
+        .replace("{date}", date)
+        .replace("{width}", &header.width.to_string())
+        .replace("{height}", &header.height.to_string())
+}
+
+/// Today's UTC date as `YYYY-MM-DD`.
+fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    civil_date((secs / 86_400) as i64)
+}
+
+/// The proleptic Gregorian date `days` after 1970-01-01, after Howard Hinnant's
+/// `civil_from_days`.
+fn civil_date(days: i64) -> String {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+pub fn encode_batch(matches: &ArgMatches) -> Result<()> {
+    let template = PathBuf::from_str(matches.get_one::<String>("template").expect("required"))?;
+    let csv = PathBuf::from_str(matches.get_one::<String>("csv").expect("required"))?;
+    let out_dir = PathBuf::from_str(matches.get_one::<String>("out-dir").expect("required"))?;
+    let chunk_type = parse_chunk_type(matches.get_one::<String>("chunk-type").expect("required"))?;
+
+    let contents = fs::read(&template)?;
+    let csv = fs::read_to_string(&csv)?;
+    let stem = template
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("encoded");
+
+    let mut stderr = io::stderr();
+    let progress: Option<&mut dyn Write> = if matches.get_flag("progress-json") {
+        Some(&mut stderr)
+    } else {
+        None
+    };
+    let verify = matches.get_flag("verify-after-write");
+    let mut write = |path: &Path, png: &mut Png| -> Result<()> {
+        if matches.get_flag("finalize") {
+            png.ensure_iend();
+        }
+        write_png(path, png, verify)
+    };
+    let written = batch::encode_batch(
+        contents.as_slice(),
+        &csv,
+        &chunk_type,
+        &out_dir,
+        stem,
+        &mut write,
+        progress,
+    )?;
+    for path in written.iter() {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+pub fn decode(matches: &ArgMatches) -> Result<()> {
+    let path = matches.get_one::<String>("PATH").expect("required");
+    let mut input: Box<dyn Read> = if path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(fs::File::open(path)?)
+    };
+    input = gzip::decompress_reader(input)?;
+    if matches.contains_id("offset") || matches.contains_id("length") || matches.contains_id("scan")
+    {
+        let mut contents = Vec::new();
+        input.read_to_end(&mut contents)?;
+        let contents = input_range(matches, &contents)?.to_vec();
+        input = Box::new(io::Cursor::new(contents));
+    }
+    let mut input = BufReader::new(input);
+
+    let chunk_types = chunk_type_args(matches)?;
+    let chunk_types: Vec<&str> = chunk_types.iter().map(|s| s.as_str()).collect();
+    let ignore_bits = matches.get_flag("ignore-bits");
+    let codecs = codecs(matches)?;
+    let decoded = |chunk: &Chunk| -> Result<Chunk> {
+        let mut data = codecs.decode(chunk.data())?;
+        if let Some(key) = matches.get_one::<String>("named") {
+            data = Chunk::unpack_messages(&data)?
+                .into_iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, message)| message)
+                .ok_or_else(|| format!("no message named '{}'", key))?;
+        }
+        Ok(Chunk::new(*chunk.chunk_type(), data))
+    };
+    let message = |chunk: &Chunk| -> Result<String> {
+        let text = if matches.get_flag("escape") {
+            escape_bytes(chunk.data())
+        } else if matches.get_flag("base64") {
+            String::from_utf8(codec::Base64.encode(chunk.data()))?
+        } else if matches.get_flag("hex") {
+            hex::encode(chunk.data())
+        } else {
+            render_data(chunk)?
+        };
+        Ok(match matches.get_one::<usize>("wrap") {
+            Some(width) => wrap(&text, *width),
+            None => text,
+        })
+    };
+
+    if matches.contains_id("output-file") && chunk_types.len() > 1 {
+        let e: Box<dyn std::error::Error> =
+            String::from("--output-file takes a single chunk type").into();
+        return Err(e);
+    }
+
+    if matches.get_flag("stream-output") {
+        let [chunk_type] = chunk_types[..] else {
+            let e: Box<dyn std::error::Error> =
+                String::from("--stream-output takes a single chunk type").into();
+            return Err(e);
+        };
+        return stream_message(
+            input,
+            chunk_type,
+            ignore_bits,
+            &codecs,
+            &mut io::stdout().lock(),
+        );
+    }
+
+    if let [chunk_type] = chunk_types[..] {
+        let chunk = decoded(&find_message_in_stream(
+            &mut input,
+            chunk_type,
+            ignore_bits,
+        )?)?;
+
+        if matches.get_flag("raw") {
+            write_raw(&mut io::stdout().lock(), &chunk)?;
+            return Ok(());
+        }
+        if let Some(file) = matches.get_one::<String>("output-file") {
+            fs::write(file, chunk.data())?;
+            return Ok(());
+        }
+
+        // Carry on through the stream for further chunks of the same type.
+        let wanted = parse_chunk_type(chunk_type)?;
+        let mut more = Vec::new();
+        while let Some(next) = Chunk::from_reader(&mut input)? {
+            if type_matches(next.chunk_type(), &wanted, ignore_bits) {
+                more.push(decoded(&next)?);
+            }
+        }
+        if more.is_empty() {
+            println!("Message: {}", message(&chunk)?);
+        } else {
+            for (i, chunk) in std::iter::once(chunk).chain(more).enumerate() {
+                println!("Message {}: {}", i + 1, message(&chunk)?);
+            }
+        }
+        return Ok(());
+    }
+
+    let mut contents = Vec::new();
+    input.read_to_end(&mut contents)?;
+    let png = Png::try_from(contents.as_slice())?;
+
+    // With several types, label each message and report missing ones instead of failing.
+    let chunks = find_messages(&png, &chunk_types, ignore_bits)?;
+    for (chunk_type, chunk) in chunk_types.iter().zip(chunks) {
+        match chunk.map(decoded).transpose()? {
+            Some(chunk) if matches.get_flag("raw") => write_raw(&mut io::stdout().lock(), &chunk)?,
+            Some(chunk) => println!("{}: {}", chunk_type, message(&chunk)?),
+            None => eprintln!("{}: no message found", chunk_type),
+        }
+    }
+    Ok(())
+}
+
+/// The codecs for the data: `compressed` for `--compress`/`--decompress`, then
+/// the cipher for `--password`, then those named by `--codec` in the order given.
+/// Markers are written by `encode --marker` and detected by `decode --detect-marker`.
+fn codecs(matches: &ArgMatches) -> Result<Pipeline> {
+    let flag = |name: &str| matches.try_get_one::<bool>(name).ok().flatten() == Some(&true);
+    let compress = flag("compress") || flag("decompress");
+    // Both sides only use markers when asked: unmarked data may well start with 0xf8-0xfc.
+    let markers = flag("marker") || flag("detect-marker");
+    let mut pipeline =
+        Pipeline::from_names(compress.then_some("compressed"))?.with_markers(markers);
+    if let Some(password) = matches.try_get_one::<String>("password").ok().flatten() {
+        pipeline.push(cipher(password, matches)?);
+    }
+    for name in matches.get_many::<String>("codec").unwrap_or_default() {
+        pipeline.push(codec::codec_by_name(name)?);
+    }
+    Ok(pipeline)
+}
+
+#[cfg(feature = "encryption")]
+fn cipher(password: &str, matches: &ArgMatches) -> Result<Box<dyn DataCodec>> {
+    use crate::cipher::{Cipher, KeyParams};
+
+    let path = |name: &str| matches.try_get_one::<String>(name).ok().flatten();
+    let cipher = if let Some(key_file) = path("key-file") {
+        Cipher::with_key_params(
+            password,
+            KeyParams::from_keyfile(&fs::read_to_string(key_file)?)?,
+        )
+    } else if let Some(key_file) = path("emit-key") {
+        // Written before the PNG is: a message whose keyfile was lost can't be decrypted.
+        let params = KeyParams::generate();
+        fs::write(key_file, params.to_keyfile())?;
+        Cipher::with_key_params(password, params)
+    } else {
+        Cipher::new(password)
+    };
+    Ok(Box::new(cipher))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn cipher(_password: &str, _matches: &ArgMatches) -> Result<Box<dyn DataCodec>> {
+    Err(String::from("--password needs pngme to be built with the encryption feature").into())
+}
+
+/// The chunk types named by CHUNK_TYPE, or the one given by `--chunk-type-bytes`.
+fn chunk_type_args(matches: &ArgMatches) -> Result<Vec<String>> {
+    match matches.get_one::<String>("chunk-type-bytes") {
+        Some(list) => Ok(vec![parse_type_bytes(list)?.to_string()]),
+        None => Ok(matches
+            .get_many::<String>("CHUNK_TYPE")
+            .expect("required")
+            .cloned()
+            .collect()),
+    }
+}
+
+/// Parse a chunk type written as four comma-separated decimal bytes, like `82,117,83,116`.
+pub fn parse_type_bytes(list: &str) -> Result<ChunkType> {
+    let values = list
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<u8>()
+                .map_err(|_| format!("'{}' is not a byte value between 0 and 255", v.trim()))
+        })
+        .collect::<std::result::Result<Vec<u8>, String>>()?;
+    let bytes: [u8; 4] = values
+        .try_into()
+        .map_err(|v: Vec<u8>| format!("chunk type needs exactly 4 byte values, got {}", v.len()))?;
+    Ok(ChunkType::try_from(bytes)?)
+}
+
+pub fn remove(matches: &ArgMatches) -> Result<()> {
+    let (path, mut png) = read_png(matches)?;
+
+    let chunk_type = &chunk_type_args(matches)?[0];
+    let what = match chunk_type.as_str() {
+        // `@critical` keeps the critical chunks, which is the same as stripping the ancillary ones.
+        "*" | "@ancillary" | "@critical" => {
+            format!("removed {} ancillary chunks", png.keep_only_critical())
+        }
+        // Reserve the rest of the `@` namespace for future tokens.
+        token if token.starts_with('@') => {
+            let e: Box<dyn std::error::Error> = format!(
+                "unknown chunk type token '{}'; use '@ancillary', '@critical' or '*' to remove all ancillary chunks",
+                token
+            )
+            .into();
+            return Err(e);
+        }
+        _ if matches.get_flag("all") => {
+            let wanted = parse_chunk_type(chunk_type)?;
+            let ignore_bits = matches.get_flag("ignore-bits");
+            let removed = png.remove_chunks(|c| type_matches(c.chunk_type(), &wanted, ignore_bits));
+            if removed == 0 {
+                let e: Box<dyn std::error::Error> =
+                    PngError::ChunkNotFound(chunk_type.to_string()).into();
+                return Err(e);
+            }
+            format!("removed {} '{}' chunks", removed, chunk_type)
+        }
+        _ => {
+            let chunk = remove_message(&mut png, chunk_type, matches.get_flag("ignore-bits"))?;
+            format!(
+                "removed '{}' ({} bytes)",
+                chunk.chunk_type(),
+                chunk.length()
+            )
+        }
+    };
+
+    write_in_place(&path, &mut png, matches)?;
+    let what = format!("{} from {}", what, destination(&path, matches));
+    status(matches, &change_summary(&what, &png));
+    Ok(())
+}
+
+pub fn print(matches: &ArgMatches) -> Result<()> {
+    let png = read_input_png(matches)?;
+
+    if matches.get_flag("csv") {
+        let file = matches.get_one::<String>("PATH").expect("required");
+        let chunks = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.chunk_type().is_public());
+        return write_chunk_csv(&mut io::stdout().lock(), file, chunks);
+    }
+
+    if wants_json(matches) {
+        let chunks = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.chunk_type().is_public());
+        println!("{}", chunks_json(chunks, true)?);
+        return Ok(());
+    }
+
+    let chunks: Vec<&Chunk> = png
+        .chunks()
+        .iter()
+        .filter(|c| !c.chunk_type().is_public())
+        .collect();
+
+    if matches.get_flag("escape") {
+        println!("Chunks:");
+        for chunk in chunks.iter() {
+            if chunk.data_utf8_valid() {
+                println!("{}: {}", chunk.chunk_type(), escape_bytes(chunk.data()));
+            } else {
+                println!("{}: 0x{}", chunk.chunk_type(), hex::encode(chunk.data()));
+            }
+        }
+    } else if matches.get_flag("crc-hex") {
+        println!("Chunks:");
+        for chunk in chunks.iter() {
+            print!("{:#}", chunk);
+        }
+    } else {
+        println!("Chunks: {:#?}", chunks);
+    }
+
+    Ok(())
+}
+
+pub fn move_chunk(matches: &ArgMatches) -> Result<()> {
+    let (path, mut png) = read_png(matches)?;
+
+    let from = *matches.get_one::<usize>("FROM").expect("required");
+    let to = *matches.get_one::<usize>("TO").expect("required");
+
+    png.move_chunk(from, to)?;
+
+    write_in_place(&path, &mut png, matches)
+}
+
+pub fn strip_before_idat(matches: &ArgMatches) -> Result<()> {
+    let (path, mut png) = read_png(matches)?;
+
+    let removed = png.remove_ancillary_before_idat();
+    status(matches, &format!("Removed {} chunks", removed));
+
+    write_in_place(&path, &mut png, matches)
+}
+
+pub fn minify(matches: &ArgMatches) -> Result<()> {
+    let (path, mut png) = read_png(matches)?;
+
+    let removed = png.keep_only_critical();
+    status(matches, &format!("Removed {} chunks", removed));
+
+    write_in_place(&path, &mut png, matches)
+}
+
+pub fn split(matches: &ArgMatches) -> Result<()> {
+    let path = PathBuf::from_str(matches.get_one::<String>("PATH").expect("required"))?;
+    let out_dir = PathBuf::from_str(matches.get_one::<String>("OUT_DIR").expect("required"))?;
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("split");
+
+    let pngs = Png::split_concatenated(&fs::read(&path)?)?;
+    fs::create_dir_all(&out_dir)?;
+    for (i, png) in pngs.iter().enumerate() {
+        let out = out_dir.join(format!("{}-{}.png", stem, i + 1));
+        write_png(&out, png, matches.get_flag("verify-after-write"))?;
+        println!("{}", out.display());
+    }
+    Ok(())
+}
+
+pub fn dedup(matches: &ArgMatches) -> Result<()> {
+    let (path, mut png) = read_png(matches)?;
+
+    let removed = png.dedup_chunks();
+    status(matches, &format!("Removed {} chunks", removed));
+
+    write_in_place(&path, &mut png, matches)
+}
+
+pub fn pipe(matches: &ArgMatches) -> Result<()> {
+    let (path, mut png) = read_png(matches)?;
+
+    let ops = matches
+        .get_many::<String>("do")
+        .expect("required")
+        .map(|s| pipe::Op::from_str(s))
+        .collect::<Result<Vec<_>>>()?;
+
+    pipe::run(&mut png, &ops)?;
+
+    write_in_place(&path, &mut png, matches)
+}
+
+pub fn info(matches: &ArgMatches) -> Result<()> {
+    let png = read_input_png(matches)?;
+    if wants_json(matches) {
+        println!("{}", info_json(&png)?);
+        return Ok(());
+    }
+
+    let header = png.ihdr()?;
+    println!("Width: {}", header.width);
+    println!("Height: {}", header.height);
+    println!("Bit depth: {}", header.bit_depth);
+    println!("Color type: {}", header.color_type);
+    println!("Compression method: {}", header.compression);
+    println!("Filter method: {}", header.filter);
+    println!("Interlace method: {}", header.interlace);
+    if let Some(pixels) = png.total_pixels() {
+        println!("Total pixels: {}", pixels);
+    }
+    match png.estimated_decoded_size() {
+        Some(size) => println!("Estimated decoded size: {} bytes", size),
+        None => println!("Estimated decoded size: unknown"),
+    }
+    write_palette_chunks(&mut io::stdout().lock(), &png)
+}
+
+/// Browse the chunks of PNG file interactively; needs the `tui` feature.
+pub fn browse(matches: &ArgMatches) -> Result<()> {
+    let png = read_input_png(matches)?;
+    browse::run(&png)
+}
+
+pub fn profile(matches: &ArgMatches) -> Result<()> {
+    let png = read_input_png(matches)?;
+    write_profile(&mut io::stdout().lock(), &png.size_histogram())
+}
+
+pub fn count(matches: &ArgMatches) -> Result<()> {
+    let png = read_input_png(matches)?;
+    let mut counts = png.type_counts();
+    if let Some(chunk_type) = matches.get_one::<String>("CHUNK_TYPE") {
+        let chunk_type = parse_chunk_type(chunk_type)?.to_string();
+        let count = counts.remove(&chunk_type).unwrap_or(0);
+        counts = BTreeMap::from([(chunk_type, count)]);
+    }
+    write_counts(&mut io::stdout().lock(), &counts)
+}
+
+pub fn dump_signature(matches: &ArgMatches) -> Result<()> {
+    let path = PathBuf::from_str(matches.get_one::<String>("PATH").expect("required"))?;
+    let contents = fs::read(&path)?;
+    write_signature(&mut io::stdout().lock(), &contents)
+}
+
+pub fn list(matches: &ArgMatches) -> Result<()> {
+    let png = read_input_png(matches)?;
+    if matches.get_flag("csv") {
+        let file = matches.get_one::<String>("PATH").expect("required");
+        return write_chunk_csv(
+            &mut io::stdout().lock(),
+            file,
+            png.chunks().iter().enumerate(),
+        );
+    }
+    if wants_json(matches) {
+        println!("{}", chunks_json(png.chunks().iter().enumerate(), false)?);
+        return Ok(());
+    }
+    write_chunk_list(&mut io::stdout().lock(), &png)
+}
+
+pub fn chunk_types(_matches: &ArgMatches) -> Result<()> {
+    write_chunk_types(&mut io::stdout().lock())
+}
+
+/// Correct chunk length fields that disagree with their CRCs, reporting each fix.
+pub fn repair_lengths(matches: &ArgMatches) -> Result<()> {
+    let path = PathBuf::from_str(matches.get_one::<String>("PATH").expect("required"))?;
+    let (contents, mismatches) = Png::repair_lengths(&read_path(&path)?);
+    for m in &mismatches {
+        status(
+            matches,
+            &format!(
+                "{} at offset {}: length {} corrected to {}",
+                m.chunk_type, m.offset, m.declared, m.actual
+            ),
+        );
+    }
+    if mismatches.is_empty() {
+        status(matches, "unchanged");
+        // Leave the file alone when editing in place, but still copy it to stdout or --output.
+        let in_place = !matches.contains_id("output") && path != Path::new(STDIO);
+        if in_place {
+            return Ok(());
+        }
+    }
+
+    let mut png = parse_input(matches, &contents)?;
+    write_in_place(&path, &mut png, matches)
+}
+
+/// Report each chunk whose stored CRC doesn't match its data, returning 1 if any don't.
+pub fn verify(matches: &ArgMatches) -> Result<i32> {
+    let path = PathBuf::from_str(matches.get_one::<String>("PATH").expect("required"))?;
+    let png = Png::try_from_lenient(&read_path(&path)?)?;
+
+    let bad = write_crc_report(&mut io::stdout().lock(), &png)?;
+    Ok(if bad == 0 { 0 } else { 1 })
+}
+
+/// Compare PATH with `--against` chunk by chunk, skipping `--ignore` types, and
+/// report the first difference. Returns 0 if they match and 1 if not.
+pub fn verify_file(matches: &ArgMatches) -> Result<i32> {
+    let png = read_input_png(matches)?;
+    let reference = PathBuf::from_str(matches.get_one::<String>("against").expect("required"))?;
+    let reference = Png::try_from(gzip::decompress(read_path(&reference)?)?.as_slice())?;
+    let ignore = matches
+        .get_many::<String>("ignore")
+        .unwrap_or_default()
+        .map(|t| {
+            let chunk_type = parse_chunk_type(t)?;
+            if chunk_type.is_critical() {
+                return Err(format!("can't ignore critical chunk type {}", chunk_type).into());
+            }
+            Ok(chunk_type)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let diff = png.first_difference(&reference, &ignore);
+    write_difference(&mut io::stdout().lock(), diff.as_ref())?;
+    Ok(if diff.is_none() { 0 } else { 1 })
+}
+
+fn write_difference<W: Write>(out: &mut W, diff: Option<&ChunkDiff>) -> Result<()> {
+    match diff {
+        None => writeln!(out, "equivalent to the reference")?,
+        Some(ChunkDiff::Changed {
+            index,
+            other_index,
+            chunk_type,
+            other_type,
+        }) => writeln!(
+            out,
+            "chunk {} ({}) differs from reference chunk {} ({})",
+            index, chunk_type, other_index, other_type
+        )?,
+        Some(ChunkDiff::Extra { index, chunk_type }) => writeln!(
+            out,
+            "chunk {} ({}) is not in the reference",
+            index, chunk_type
+        )?,
+        Some(ChunkDiff::Missing {
+            other_index,
+            chunk_type,
+        }) => writeln!(
+            out,
+            "reference chunk {} ({}) is missing",
+            other_index, chunk_type
+        )?,
+    }
+    Ok(())
+}
+
+/// List the chunks with CRC mismatches along with their byte offsets, returning how many there were.
+fn write_crc_report<W: Write>(out: &mut W, png: &Png) -> Result<usize> {
+    let mut bad = 0;
+    for (offset, chunk) in png.iter() {
+        if !chunk.is_crc_valid() {
+            writeln!(
+                out,
+                "{} at offset {}: stored crc {:08x} doesn't match its data",
+                chunk.chunk_type(),
+                offset,
+                chunk.crc()
+            )?;
+            bad += 1;
+        }
+    }
+    if bad == 0 {
+        writeln!(out, "all {} chunk CRCs are valid", png.chunks().len())?;
+    }
+    Ok(bad)
+}
+
+/// Print the lint findings, returning the exit code for the worst of them.
+pub fn lint(matches: &ArgMatches) -> Result<i32> {
+    let path = matches.get_one::<String>("PATH").expect("required");
+    let contents = gzip::decompress(read_path(Path::new(path))?)?;
+    // Lint the rest as if wrong lengths were fixed, since the PNG won't parse otherwise.
+    let (contents, mismatches) = Png::repair_lengths(input_range(matches, &contents)?);
+    let png = parse_input(matches, &contents)?;
+
+    let mut findings = lint::length_findings(&mismatches);
+    findings.extend(lint::lint(&png));
+    if matches.get_flag("json") {
+        println!("{}", lint::findings_to_json(&findings));
+    } else {
+        for finding in findings.iter() {
+            println!("{}", finding);
+        }
+    }
+
+    Ok(lint::exit_code(&findings))
+}
+
+/// Exit code 0 when the PNG has a chunk of the type and 1 when it doesn't, printing nothing.
+pub fn has(matches: &ArgMatches) -> Result<i32> {
+    let chunk_type = parse_chunk_type(matches.get_one::<String>("CHUNK_TYPE").expect("required"))?;
+    let png = read_input_png(matches)?;
+
+    Ok(if png.contains_chunk_type(&chunk_type) {
+        0
+    } else {
+        1
+    })
+}
+
+/// Overwrite `path` with `png`, asking first when a user is at the terminal. With
+/// `--output`, write there instead, asking only if that file already exists.
+fn write_in_place(path: &Path, png: &mut Png, matches: &ArgMatches) -> Result<()> {
+    // Only some write commands define --output.
+    let output = matches.try_get_one::<String>("output").ok().flatten();
+    let path = output.map_or(path, Path::new);
+    let interactive = io::stdin().is_terminal();
+    if path != Path::new(STDIO)
+        && (output.is_none() || path.exists())
+        && !confirm_overwrite(
+            path,
+            matches.get_flag("assume-yes"),
+            interactive,
+            &mut io::stdin().lock(),
+            &mut io::stderr(),
+        )?
+    {
+        let e: Box<dyn std::error::Error> =
+            format!("aborted: {} was not modified", path.display()).into();
+        return Err(e);
+    }
+    if matches.get_flag("finalize") {
+        png.ensure_iend();
+    }
+    if path == Path::new(STDIO) {
+        let mut stdout = io::stdout().lock();
+        png.write_to(&mut stdout)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+    write_png(path, png, matches.get_flag("verify-after-write"))
+}
+
+/// Stream `png` to `path` through a buffer rather than serializing it in memory first;
+/// with `verify`, read the file back and make sure it parses.
+fn write_png(path: &Path, png: &Png, verify: bool) -> Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    png.write_to(&mut out)?;
+    out.flush()?;
+    if verify {
+        verify_written(path)?;
+    }
+    Ok(())
+}
+
+fn verify_written(path: &Path) -> Result<()> {
+    let written = fs::read(path)?;
+    if let Err(e) = Png::try_from(written.as_slice()) {
+        return Err(format!(
+            "{} failed verification after writing: {}",
+            path.display(),
+            e
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Scripts (no TTY) and `--assume-yes` proceed without prompting.
+fn confirm_overwrite<R: BufRead, W: Write>(
+    path: &Path,
+    assume_yes: bool,
+    interactive: bool,
+    input: &mut R,
+    output: &mut W,
+) -> Result<bool> {
+    if assume_yes || !interactive {
+        return Ok(true);
+    }
+    write!(output, "Overwrite {}? [y/N] ", path.display())?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// One line per chunk, in file order: index, type, data length, CRC and criticality.
+fn write_chunk_list<W: Write>(out: &mut W, png: &Png) -> Result<()> {
+    for (i, chunk) in png.chunks().iter().enumerate() {
+        writeln!(
+            out,
+            "{:>3}  {}  {:>10} bytes  crc {:08x}  {}",
+            i,
+            chunk.chunk_type(),
+            chunk.length(),
+            chunk.crc(),
+            if chunk.chunk_type().is_critical() {
+                "critical"
+            } else {
+                "ancillary"
+            }
+        )?;
+    }
+    Ok(())
+}
+
+/// One CSV row per chunk, with a header row, for spreadsheets and other tabular tools.
+fn write_chunk_csv<'a, W: Write, I: Iterator<Item = (usize, &'a Chunk)>>(
+    out: &mut W,
+    file: &str,
+    chunks: I,
+) -> Result<()> {
+    writeln!(
+        out,
+        "file,index,type,length,crc,critical,public,safe_to_copy"
+    )?;
+    for (i, chunk) in chunks {
+        let chunk_type = chunk.chunk_type();
+        writeln!(
+            out,
+            "{}",
+            csv::row(&[
+                file,
+                &i.to_string(),
+                &chunk_type.to_string(),
+                &chunk.length().to_string(),
+                &format!("{:08x}", chunk.crc()),
+                &chunk_type.is_critical().to_string(),
+                &chunk_type.is_public().to_string(),
+                &chunk_type.is_safe_to_copy().to_string(),
+            ])
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether `--format json` was asked for.
+fn wants_json(matches: &ArgMatches) -> bool {
+    matches.get_one::<String>("format").map(String::as_str) == Some("json")
+}
+
+/// One chunk's metadata as a JSON object, with its rendered data when `with_data` is set.
+fn chunk_json(index: usize, chunk: &Chunk, with_data: bool) -> Result<String> {
+    let chunk_type = chunk.chunk_type();
+    let data = if with_data {
+        format!(",\"data\":{}", json::quote(&render_data(chunk)?))
+    } else {
+        String::new()
+    };
+    Ok(format!(
+        "{{\"index\":{},\"type\":{},\"length\":{},\"crc\":{},\"crc_valid\":{},\"critical\":{},\"public\":{},\"safe_to_copy\":{}{}}}",
+        index,
+        json::quote(&chunk_type.to_string()),
+        chunk.length(),
+        chunk.crc(),
+        chunk.is_crc_valid(),
+        chunk_type.is_critical(),
+        chunk_type.is_public(),
+        chunk_type.is_safe_to_copy(),
+        data
+    ))
+}
+
+fn chunks_json<'a, I: Iterator<Item = (usize, &'a Chunk)>>(
+    chunks: I,
+    with_data: bool,
+) -> Result<String> {
+    let items = chunks
+        .map(|(i, chunk)| chunk_json(i, chunk, with_data))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!("{{\"chunks\":[{}]}}", items.join(",")))
+}
+
+/// The `info` output as a JSON object, with `null` for sizes that can't be worked out.
+fn info_json(png: &Png) -> Result<String> {
+    let header = png.ihdr()?;
+    let or_null = |n: Option<u64>| n.map_or(String::from("null"), |n| n.to_string());
+    Ok(format!(
+        "{{\"width\":{},\"height\":{},\"bit_depth\":{},\"color_type\":{},\"compression\":{},\"filter\":{},\"interlace\":{},\"total_pixels\":{},\"estimated_decoded_size\":{}}}",
+        header.width,
+        header.height,
+        header.bit_depth,
+        header.color_type,
+        header.compression,
+        header.filter,
+        header.interlace,
+        or_null(png.total_pixels()),
+        or_null(png.estimated_decoded_size())
+    ))
+}
+
+/// List the standard chunk types along with what their property bits mean.
+fn write_chunk_types<W: Write>(out: &mut W) -> Result<()> {
+    for (name, description) in chunk_type::KNOWN_TYPES.iter() {
+        let chunk_type = ChunkType::from_str(name)?;
+        writeln!(
+            out,
+            "{}  {:<9} {:<7} {:<14} {}",
+            chunk_type,
+            if chunk_type.is_critical() {
+                "critical"
+            } else {
+                "ancillary"
+            },
+            if chunk_type.is_public() {
+                "public"
+            } else {
+                "private"
+            },
+            if chunk_type.is_safe_to_copy() {
+                "safe-to-copy"
+            } else {
+                "unsafe-to-copy"
+            },
+            description
+        )?;
+    }
+    Ok(())
+}
+
+/// Show the first 8 bytes of a file and whether they are the PNG signature.
+fn write_signature<W: Write>(out: &mut W, contents: &[u8]) -> Result<()> {
+    let signature = &contents[..contents.len().min(8)];
+    let hex: Vec<String> = signature.iter().map(|b| format!("{:02X}", b)).collect();
+    writeln!(out, "Signature: {}", hex.join(" "))?;
+    if signature == Png::STANDARD_HEADER {
+        writeln!(out, "Matches the PNG signature")?;
+    } else {
+        let expected: Vec<String> = Png::STANDARD_HEADER
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        writeln!(
+            out,
+            "Does not match the PNG signature ({})",
+            expected.join(" ")
+        )?;
+    }
+    Ok(())
+}
+
+/// List the palette-related chunks present and how large they are.
+fn write_palette_chunks<W: Write>(out: &mut W, png: &Png) -> Result<()> {
+    for chunk in png.chunks().iter() {
+        if let b"PLTE" | b"sBIT" | b"hIST" | b"sPLT" = &chunk.chunk_type().bytes() {
+            writeln!(out, "{}: {} bytes", chunk.chunk_type(), chunk.length())?;
+        }
+    }
+    Ok(())
+}
+
+/// Draw the size histogram as bars scaled to the largest total.
+fn write_profile<W: Write>(out: &mut W, histogram: &[(ChunkType, u64)]) -> Result<()> {
+    const WIDTH: u64 = 40;
+    let max = histogram.iter().map(|(_, size)| *size).max().unwrap_or(0);
+    for (chunk_type, size) in histogram.iter() {
+        let bar = (size * WIDTH).checked_div(max).unwrap_or(0);
+        writeln!(
+            out,
+            "{}  {:<40}  {} bytes",
+            chunk_type,
+            "#".repeat(bar as usize),
+            size
+        )?;
+    }
+    Ok(())
+}
+
+fn write_counts<W: Write>(out: &mut W, counts: &BTreeMap<String, usize>) -> Result<()> {
+    for (chunk_type, count) in counts.iter() {
+        writeln!(out, "{}  {}", chunk_type, count)?;
+    }
+    Ok(())
+}
+
+/// A chunk's data as text when it is UTF-8, otherwise as `0x`-prefixed hex.
+fn render_data(chunk: &Chunk) -> Result<String> {
+    if chunk.data_utf8_valid() {
+        chunk.data_as_string()
+    } else {
+        Ok(format!("0x{}", hex::encode(chunk.data())))
+    }
+}
+
+/// Hard-wrap `text` so no line is longer than `width` characters. Existing
+/// line breaks are kept; a width of 0 leaves the text alone.
+fn wrap(text: &str, width: usize) -> String {
+    if width == 0 {
+        return text.to_string();
+    }
+    let lines: Vec<String> = text
+        .split('\n')
+        .flat_map(|line| {
+            let chars: Vec<char> = line.chars().collect();
+            if chars.is_empty() {
+                return vec![String::new()];
+            }
+            chars
+                .chunks(width)
+                .map(|chunk| chunk.iter().collect())
+                .collect()
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Write a chunk's data with no prefix, conversion, or trailing newline.
+fn write_raw<W: Write>(out: &mut W, chunk: &Chunk) -> io::Result<()> {
+    out.write_all(chunk.data())?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args;
+
+    fn testing_png() -> Png {
+        let chunk = Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]);
+        Png::from_chunks(vec![chunk])
+    }
+
+    /// Write `png` to a fresh temp file, returning its path.
+    fn temp_png(name: &str, png: &Png) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("pngme-{}-{}.png", name, std::process::id()));
+        fs::write(&path, png.as_bytes()).unwrap();
+        path
+    }
+
+    /// The sub-matches for a command line, as `main` would dispatch them.
+    fn sub_matches(argv: &[&str]) -> ArgMatches {
+        let matches = args::cli()
+            .try_get_matches_from(std::iter::once("pngme").chain(argv.iter().copied()))
+            .unwrap();
+        matches.subcommand().unwrap().1.clone()
+    }
+
+    fn read(path: &Path) -> Png {
+        Png::try_from(fs::read(path).unwrap().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_encode_decode_message() {
+        let mut png = testing_png();
+        encode_message(&mut png, "ruSt", "hello").unwrap();
+
+        assert_eq!(decode_message(&png, "ruSt", false).unwrap(), "hello");
+        assert!(decode_message(&png, "RuSt", false).is_err());
+        assert_eq!(decode_message(&png, "RuSt", true).unwrap(), "hello");
+        assert!(encode_message(&mut png, "ru5t", "hello").is_err());
+    }
+
+    #[test]
+    fn test_bad_chunk_type_errors_match() {
+        let expected = "invalid chunk type 'ru5t': Invalid ChunkType";
+        let mut png = testing_png();
+        encode_message(&mut png, "ruSt", "hello").unwrap();
+
+        let errors = [
+            encode_message(&mut png, "ru5t", "hello").unwrap_err(),
+            decode_message(&png, "ru5t", false).unwrap_err(),
+            remove_message(&mut png, "ru5t", false).unwrap_err(),
+        ];
+        for e in errors {
+            assert_eq!(e.to_string(), expected);
+        }
+        assert_eq!(
+            parse_chunk_type("RuS").unwrap_err().to_string(),
+            "invalid chunk type 'RuS': chunk type must be 4 bytes, got 3"
+        );
+
+        let path = std::env::temp_dir().join("pngme-commands-bad-chunk-type.png");
+        fs::write(&path, png.as_bytes()).unwrap();
+        let p = path.to_str().unwrap();
+        let errors = [
+            encode(&sub_matches(&["encode", p, "ru5t", "hi"])).unwrap_err(),
+            decode(&sub_matches(&["decode", p, "ru5t"])).unwrap_err(),
+            remove(&sub_matches(&["remove", p, "ru5t", "-y"])).unwrap_err(),
+        ];
+        for e in errors {
+            assert_eq!(e.to_string(), expected);
+        }
+        assert_eq!(read(&path).as_bytes(), png.as_bytes());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_decode_messages() {
+        let mut png = testing_png();
+        encode_message(&mut png, "ruSt", "first").unwrap();
+        encode_message(&mut png, "geMs", "second").unwrap();
+
+        let messages = decode_messages(&png, &["geMs", "miSs", "ruSt"], false).unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                Some(String::from("second")),
+                None,
+                Some(String::from("first"))
+            ]
+        );
+        assert!(decode_messages(&png, &["ruSt", "ru5t"], false).is_err());
+    }
+
+    /// A reader that fails once more than `limit` bytes have been read from it.
+    struct CappedReader<R> {
+        inner: R,
+        limit: usize,
+        read: usize,
+    }
+
+    impl<R: Read> Read for CappedReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n;
+            if self.read > self.limit {
+                return Err(io::Error::other("read past the cap"));
+            }
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_stream_message_reads_only_to_the_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "early").unwrap());
+        for _ in 0..64 {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str("IDAT").unwrap(),
+                vec![0; 64 * 1024],
+            ));
+        }
+        let bytes = png.as_bytes();
+        assert!(bytes.len() > 4 * 1024 * 1024);
+
+        let input = CappedReader {
+            inner: bytes.as_slice(),
+            limit: 64 * 1024,
+            read: 0,
+        };
+        let mut out = Vec::new();
+        stream_message(
+            BufReader::new(input),
+            "ruSt",
+            false,
+            &Pipeline::default(),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(out, b"early");
+
+        let input = CappedReader {
+            inner: bytes.as_slice(),
+            limit: 64 * 1024,
+            read: 0,
+        };
+        assert!(stream_message(input, "teSt", false, &Pipeline::default(), &mut out).is_err());
+    }
+
+    #[test]
+    fn test_find_message_in_stream() {
+        let mut png = testing_png();
+        encode_message(&mut png, "ruSt", "hello").unwrap();
+        let bytes = png.as_bytes();
+
+        let chunk = find_message_in_stream(bytes.as_slice(), "RUST", true).unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "hello");
+        assert!(find_message_in_stream(bytes.as_slice(), "RUST", false).is_err());
+    }
+
+    #[test]
+    fn test_encode_text() {
+        let mut png = testing_png();
+        encode_text(&mut png, "Comment", "hello").unwrap();
+
+        let chunk = find_message(&png, "tEXt", false).unwrap();
+        assert_eq!(chunk.data(), b"Comment\0hello");
+    }
+
+    #[test]
+    fn test_remove_message() {
+        let mut png = testing_png();
+        encode_message(&mut png, "ruSt", "hello").unwrap();
+
+        let removed = remove_message(&mut png, "RUST", true).unwrap();
+        assert_eq!(removed.data_as_string().unwrap(), "hello");
+        assert_eq!(png.chunks().len(), 1);
+        assert!(remove_message(&mut png, "ruSt", false).is_err());
+    }
+
+    #[test]
+    fn test_encode_and_remove_commands() {
+        let path = temp_png("commands-encode", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&["encode", p, "ruSt", "hello", "-y"])).unwrap();
+        assert_eq!(
+            decode_message(&read(&path), "ruSt", false).unwrap(),
+            "hello"
+        );
+        decode(&sub_matches(&["decode", p, "ruSt"])).unwrap();
+        decode(&sub_matches(&["decode", p, "ruSt", "IHDR", "miSs"])).unwrap();
+
+        remove(&sub_matches(&["remove", p, "RUST", "--ignore-bits", "-y"])).unwrap();
+        assert_eq!(read(&path).chunks().len(), 1);
+        assert!(decode(&sub_matches(&["decode", p, "ruSt"])).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_scan_skips_leading_garbage() {
+        let path = std::env::temp_dir().join("pngme-commands-scan.png");
+        let bytes: Vec<u8> = (0..16).chain(testing_png().as_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+        let p = path.to_str().unwrap();
+
+        assert!(read_input_png(&sub_matches(&["list", p])).is_err());
+        let png = read_input_png(&sub_matches(&["list", p, "--scan"])).unwrap();
+        assert_eq!(png.as_bytes(), testing_png().as_bytes());
+        let Err(err) = read_input_png(&sub_matches(&["list", p, "--scan", "8"])) else {
+            panic!("the signature is past the scan limit");
+        };
+        assert_eq!(err.to_string(), "no PNG signature in the first 8 bytes");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_decode_scan_skips_leading_garbage() {
+        let mut png = testing_png();
+        encode_message(&mut png, "seCr", "found it").unwrap();
+        let path = std::env::temp_dir().join("pngme-commands-decode-scan.png");
+        let bytes: Vec<u8> = (0..16).chain(png.as_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+        let p = path.to_str().unwrap();
+        let out = std::env::temp_dir().join("pngme-commands-decode-scan.txt");
+        let o = out.to_str().unwrap();
+
+        assert!(decode(&sub_matches(&["decode", p, "seCr", "--output-file", o])).is_err());
+        decode(&sub_matches(&[
+            "decode",
+            p,
+            "seCr",
+            "--scan",
+            "--output-file",
+            o,
+        ]))
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"found it");
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn test_slice_input() {
+        let contents = b"0123456789";
+        assert_eq!(slice_input(contents, 2, Some(3)).unwrap(), b"234");
+        assert_eq!(slice_input(contents, 7, None).unwrap(), b"789");
+        assert_eq!(slice_input(contents, 10, None).unwrap(), b"");
+        assert!(slice_input(contents, 8, Some(3)).is_err());
+        assert!(slice_input(contents, 11, None).is_err());
+        assert!(slice_input(contents, 1, Some(usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_read_embedded_png() {
+        let mut png = testing_png();
+        encode_message(&mut png, "ruSt", "embedded").unwrap();
+        let embedded = png.as_bytes();
+        let mut contents = vec![0xAA; 100];
+        contents.extend_from_slice(&embedded);
+        contents.extend_from_slice(&[0xBB; 50]);
+
+        let path = std::env::temp_dir().join(format!("pngme-embedded-{}.bin", std::process::id()));
+        fs::write(&path, &contents).unwrap();
+        let p = path.to_str().unwrap();
+        let length = embedded.len().to_string();
+
+        let matches = sub_matches(&["print", p, "--offset", "100", "--length", &length]);
+        let (_, parsed) = read_png(&matches).unwrap();
+        assert_eq!(decode_message(&parsed, "ruSt", false).unwrap(), "embedded");
+        decode(&sub_matches(&[
+            "decode", p, "ruSt", "--offset", "100", "--length", &length,
+        ]))
+        .unwrap();
+
+        assert!(read_png(&sub_matches(&["print", p])).is_err());
+        assert!(read_png(&sub_matches(&["print", p, "--offset", "1000"])).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_idempotent() {
+        let path = temp_png("commands-idempotent", &testing_png());
+        let p = path.to_str().unwrap();
+        let argv = ["encode", p, "ruSt", "hello", "--idempotent", "-y"];
+
+        encode(&sub_matches(&argv)).unwrap();
+        let first = fs::read(&path).unwrap();
+        encode(&sub_matches(&argv)).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), first);
+        assert_eq!(read(&path).chunks().len(), 2);
+
+        encode(&sub_matches(&["encode", p, "ruSt", "hello", "-y"])).unwrap();
+        assert_eq!(read(&path).chunks().len(), 3);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_data_hex() {
+        let path = temp_png("commands-hex", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "--data-hex",
+            "00ff10",
+            "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        assert_eq!(
+            find_message(&png, "ruSt", false).unwrap().data(),
+            &[0, 255, 16]
+        );
+
+        assert!(encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "--data-hex",
+            "0ff",
+            "-y"
+        ]))
+        .is_err());
+        assert!(args::cli()
+            .try_get_matches_from(["pngme", "encode", p, "ruSt", "hi", "--data-hex", "00"])
+            .is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_normalize_type_case() {
+        let path = temp_png("commands-normalize", &testing_png());
+        let p = path.to_str().unwrap();
+
+        let argv = [
+            "encode",
+            p,
+            "data",
+            "hi",
+            "--normalize-type-case",
+            "ancillary",
+            "-y",
+        ];
+        encode(&sub_matches(&argv)).unwrap();
+        let png = read(&path);
+        let chunk_type = png.chunks()[1].chunk_type();
+        assert_eq!(chunk_type.to_string(), "daTa");
+        assert!(chunk_type.is_suitable_for_message());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_message_file_round_trip() {
+        let path = temp_png("commands-message-file", &testing_png());
+        let p = path.to_str().unwrap();
+        let message = std::env::temp_dir().join("pngme-commands-message-file.bin");
+        let decoded = std::env::temp_dir().join("pngme-commands-message-file.out");
+        let (m, d) = (message.to_str().unwrap(), decoded.to_str().unwrap());
+        let bytes: Vec<u8> = (0..=255).rev().collect();
+        fs::write(&message, &bytes).unwrap();
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "--message-file",
+            m,
+            "-y",
+        ]))
+        .unwrap();
+        assert_eq!(read(&path).chunk_by_type("ruSt").unwrap().data(), bytes);
+        decode(&sub_matches(&["decode", p, "ruSt", "--output-file", d])).unwrap();
+        assert_eq!(fs::read(&decoded).unwrap(), bytes);
+
+        assert!(args::cli()
+            .try_get_matches_from(["pngme", "encode", p, "ruSt", "hi", "--message-file", m])
+            .is_err());
+        assert!(decode(&sub_matches(&[
+            "decode",
+            p,
+            "ruSt",
+            "teSt",
+            "--output-file",
+            d
+        ]))
+        .is_err());
+
+        for file in [&path, &message, &decoded] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_encode_base64() {
+        let path = temp_png("commands-base64", &testing_png());
+        let p = path.to_str().unwrap();
+        let bytes: Vec<u8> = (0..=255).collect();
+        let encoded = String::from_utf8(codec::Base64.encode(&bytes)).unwrap();
+
+        encode(&sub_matches(&[
+            "encode", p, "ruSt", &encoded, "--base64", "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), bytes);
+        assert!(decode(&sub_matches(&["decode", p, "ruSt", "--base64"])).is_ok());
+        assert!(encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "not base64!",
+            "--base64",
+            "-y"
+        ]))
+        .is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_decode_hex() {
+        let path = temp_png("commands-hex", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "de ad\nBE ef 00",
+            "--hex",
+            "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        let chunk = png.chunk_by_type("ruSt").unwrap();
+        assert_eq!(chunk.data(), [0xde, 0xad, 0xbe, 0xef, 0x00]);
+        let matches = sub_matches(&["decode", p, "ruSt", "--hex"]);
+        assert!(decode(&matches).is_ok());
+        assert_eq!(hex::encode(chunk.data()), "deadbeef00");
+
+        for bad in ["abc", "zz"] {
+            assert!(encode(&sub_matches(&["encode", p, "ruSt", bad, "--hex", "-y"])).is_err());
+        }
+        assert!(args::cli()
+            .try_get_matches_from(["pngme", "decode", p, "ruSt", "--hex", "--base64"])
+            .is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(11_016), "2000-02-29");
+        assert_eq!(civil_date(20_000), "2024-10-04");
+        assert_eq!(civil_date(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn test_encode_template_message() {
+        let mut header = vec![0; 13];
+        header[..4].copy_from_slice(&640u32.to_be_bytes());
+        header[4..8].copy_from_slice(&480u32.to_be_bytes());
+        let png = Png::from_chunks(vec![Chunk::new(
+            ChunkType::from_str("IHDR").unwrap(),
+            header,
+        )]);
+        let path = temp_png("commands-template", &png);
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "--template-message",
+            "{filename} {width}x{height} on {date}",
+            "-y",
+        ]))
+        .unwrap();
+        let message = read(&path)
+            .chunk_by_type("ruSt")
+            .unwrap()
+            .data_as_string()
+            .unwrap();
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        assert!(message.starts_with(&format!("{} 640x480 on ", filename)));
+        assert_eq!(
+            message.len(),
+            filename.len() + " 640x480 on YYYY-MM-DD".len()
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_batch_uses_write_flags() {
+        let template = temp_png("commands-batch-template", &testing_png());
+        let dir = std::env::temp_dir().join(format!("pngme-commands-batch-{}", std::process::id()));
+        let csv = dir.with_extension("csv");
+        fs::write(&csv, "one,a.png\n").unwrap();
+
+        encode_batch(&sub_matches(&[
+            "encode-batch",
+            "--template",
+            template.to_str().unwrap(),
+            "--csv",
+            csv.to_str().unwrap(),
+            "--chunk-type",
+            "ruSt",
+            "--out-dir",
+            dir.to_str().unwrap(),
+            "--finalize",
+            "--verify-after-write",
+        ]))
+        .unwrap();
+        let png = read(&dir.join("a.png"));
+        assert!(png.has_iend());
+        assert_eq!(decode_message(&png, "ruSt", false).unwrap(), "one");
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&csv).unwrap();
+        fs::remove_file(&template).unwrap();
+    }
+
+    #[test]
+    fn test_encode_at() {
+        let path = temp_png("commands-at", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&["encode", p, "ruSt", "last", "-y"])).unwrap();
+        encode(&sub_matches(&[
+            "encode", p, "geMs", "first", "--at", "0", "-y",
+        ]))
+        .unwrap();
+        let types: Vec<String> = read(&path)
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["geMs", "IHDR", "ruSt"]);
+        assert!(encode(&sub_matches(&["encode", p, "geMs", "x", "--at", "9", "-y"])).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_named() {
+        let path = temp_png("commands-named", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode", p, "ruSt", "--named", "to=alice", "--named", "from=bob", "--named",
+            "note=a=b", "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        let messages = Chunk::unpack_messages(png.chunk_by_type("ruSt").unwrap().data()).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2], (String::from("note"), b"a=b".to_vec()));
+
+        assert!(decode(&sub_matches(&["decode", p, "ruSt", "--named", "from"])).is_ok());
+        assert!(decode(&sub_matches(&["decode", p, "ruSt", "--named", "cc"])).is_err());
+        assert!(encode(&sub_matches(&[
+            "encode", p, "ruSt", "--named", "oops", "-y"
+        ]))
+        .is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_as_private() {
+        let path = temp_png("commands-as-private", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "my note",
+            "hi",
+            "--as-private",
+            "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        let chunk_type = png.chunks()[1].chunk_type();
+        assert_eq!(chunk_type.to_string(), "myNo");
+        assert!(chunk_type.is_suitable_for_message());
+        assert_eq!(decode_message(&png, "myNo", false).unwrap(), "hi");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_finalize_adds_iend() {
+        let path = temp_png("commands-finalize", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&["encode", p, "ruSt", "hi", "-y"])).unwrap();
+        assert!(!read(&path).has_iend());
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "hi",
+            "--finalize",
+            "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        assert!(png.has_iend());
+        assert_eq!(png.chunks().len(), 4);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_create() {
+        let path =
+            std::env::temp_dir().join(format!("pngme-commands-create-{}.png", std::process::id()));
+        let p = path.to_str().unwrap();
+
+        create(&sub_matches(&[
+            "create", p, "--width", "3", "--height", "2", "--color", "ff8800", "-y",
+        ]))
+        .unwrap();
+        let header = read(&path).ihdr().unwrap();
+        assert_eq!((header.width, header.height), (3, 2));
+
+        assert!(create(&sub_matches(&[
+            "create", p, "--width", "3", "--height", "2", "--color", "ff88", "-y",
+        ]))
+        .is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_split() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend(testing_png().as_bytes());
+        let path = std::env::temp_dir().join(format!("pngme-joined-{}.png", std::process::id()));
+        fs::write(&path, &bytes).unwrap();
+        let out_dir = std::env::temp_dir().join(format!("pngme-split-{}", std::process::id()));
+
+        split(&sub_matches(&[
+            "split",
+            path.to_str().unwrap(),
+            out_dir.to_str().unwrap(),
+        ]))
+        .unwrap();
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        for i in 1..=2 {
+            let png = read(&out_dir.join(format!("{}-{}.png", stem, i)));
+            assert_eq!(png.as_bytes(), testing_png().as_bytes());
+        }
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_hex() {
+        let mut stdin = io::Cursor::new("0001fe7F\n");
+        let data = read_hex(&mut stdin).unwrap();
+        assert_eq!(data, vec![0x00, 0x01, 0xfe, 0x7f]);
+
+        let chunk = Chunk::new_with_type_str("ruSt", data.clone()).unwrap();
+        assert_eq!(chunk.data(), data.as_slice());
+
+        let err = read_hex(&mut io::Cursor::new("00x1")).unwrap_err();
+        assert_eq!(err.to_string(), "invalid hex digit 'x' at offset 2");
+    }
+
+    #[test]
+    fn test_has() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "hello").unwrap());
+        let path = temp_png("commands-has", &png);
+        let p = path.to_str().unwrap();
+
+        assert_eq!(has(&sub_matches(&["has", p, "ruSt"])).unwrap(), 0);
+        assert_eq!(has(&sub_matches(&["has", p, "teSt"])).unwrap(), 1);
+        assert!(has(&sub_matches(&["has", p, "ru1t"])).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_with_codecs() {
+        let path = temp_png("commands-codec", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode", p, "ruSt", "hi", "--codec", "base64", "--codec", "base64", "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        let chunk = png.chunk_by_type("ruSt").unwrap();
+        assert_eq!(chunk.data(), b"YUdrPQ==");
+        assert_eq!(
+            codecs(&sub_matches(&[
+                "decode", p, "ruSt", "--codec", "base64", "--codec", "base64"
+            ]))
+            .unwrap()
+            .decode(chunk.data())
+            .unwrap(),
+            b"hi"
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_with_marker() {
+        let path = temp_png("commands-marker", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode", p, "ruSt", "hi", "--codec", "base64", "--marker", "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        let data = png.chunk_by_type("ruSt").unwrap().data();
+        assert_eq!(data[0], 0xf9);
+        assert_eq!(
+            codecs(&sub_matches(&["decode", p, "ruSt", "--detect-marker"]))
+                .unwrap()
+                .decode(data)
+                .unwrap(),
+            b"hi"
+        );
+        assert_eq!(
+            codecs(&sub_matches(&["decode", p, "ruSt"]))
+                .unwrap()
+                .decode(data)
+                .unwrap(),
+            data
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_verbatim_decode_ignores_marker_bytes() {
+        let path = temp_png("commands-marker-bytes", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&["encode", p, "daTa", "f8 41", "--hex", "-y"])).unwrap();
+        let png = read(&path);
+        let data = png.chunk_by_type("daTa").unwrap().data();
+        assert_eq!(data, [0xf8, 0x41]);
+        for flag in ["--raw", "--hex", "--base64"] {
+            let matches = sub_matches(&["decode", p, "daTa", flag]);
+            assert_eq!(codecs(&matches).unwrap().decode(data).unwrap(), data);
+        }
+        let encrypted = [0xfc, 0x00];
+        let matches = sub_matches(&["decode", p, "daTa", "--raw"]);
+        assert_eq!(
+            codecs(&matches).unwrap().decode(&encrypted).unwrap(),
+            encrypted
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_decode_leaves_marker_like_data_alone() {
+        let path = temp_png("commands-marker-like", &testing_png());
+        let p = path.to_str().unwrap();
+        let out = std::env::temp_dir().join("pngme-commands-marker-like.bin");
+        let o = out.to_str().unwrap();
+
+        for data in ["f84142", "fa4142"] {
+            encode(&sub_matches(&[
+                "encode",
+                p,
+                "daTa",
+                "--data-hex",
+                data,
+                "-y",
+            ]))
+            .unwrap();
+            decode(&sub_matches(&["decode", p, "daTa", "--output-file", o])).unwrap();
+            assert_eq!(fs::read(&out).unwrap(), hex::decode(data).unwrap());
+            remove(&sub_matches(&["remove", p, "daTa", "-y"])).unwrap();
+        }
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn test_output_keeps_source() {
+        let source = temp_png("commands-output-src", &testing_png());
+        let output = std::env::temp_dir().join(format!("pngme-output-{}.png", std::process::id()));
+        let (s, o) = (source.to_str().unwrap(), output.to_str().unwrap());
+
+        encode(&sub_matches(&["encode", s, "ruSt", "hi", "-o", o])).unwrap();
+        assert!(read(&source).chunk_by_type("ruSt").is_none());
+        assert_eq!(decode_message(&read(&output), "ruSt", false).unwrap(), "hi");
+
+        let copy = temp_png("commands-output-copy", &testing_png());
+        remove(&sub_matches(&[
+            "remove",
+            o,
+            "ruSt",
+            "--output",
+            copy.to_str().unwrap(),
+            "-y",
+        ]))
+        .unwrap();
+        assert!(read(&output).chunk_by_type("ruSt").is_some());
+        assert!(read(&copy).chunk_by_type("ruSt").is_none());
+
+        for path in [source, output, copy] {
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_chunk_limit_warning() {
+        let mut png = testing_png();
+        for i in 0..60 {
+            png.append_chunk(message_chunk("tEXt", &format!("Comment\0{}", i)).unwrap());
+        }
+        let warning = chunk_limit_warning(&png, 50).unwrap();
+        assert!(warning.contains("60 ancillary chunks"));
+        assert!(chunk_limit_warning(&png, 60).is_none());
+        assert!(chunk_limit_warning(&testing_png(), 0).is_none());
+    }
+
+    #[test]
+    fn test_keep_crc() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "hello").unwrap());
+        let mut bytes = png.as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let path = temp_png("commands-keep-crc", &testing_png());
+        fs::write(&path, &bytes).unwrap();
+        let p = path.to_str().unwrap();
+
+        assert!(dedup(&sub_matches(&["dedup", p, "-y"])).is_err());
+        dedup(&sub_matches(&["dedup", p, "--keep-crc", "-y"])).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_writes_to_stdout() {
+        assert!(writes_to_stdout(&sub_matches(&[
+            "encode", "-", "ruSt", "hi"
+        ])));
+        assert!(writes_to_stdout(&sub_matches(&[
+            "remove", "in.png", "ruSt", "-o", "-"
+        ])));
+        assert!(!writes_to_stdout(&sub_matches(&[
+            "encode", "-", "ruSt", "hi", "-o", "out.png"
+        ])));
+        assert!(!writes_to_stdout(&sub_matches(&[
+            "encode", "in.png", "ruSt", "hi"
+        ])));
+        assert!(!writes_to_stdout(&sub_matches(&["dedup", "in.png"])));
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_encode_compress() {
+        let path = temp_png("commands-compress", &testing_png());
+        let p = path.to_str().unwrap();
+        let message = "all work and no play ".repeat(50);
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            &message,
+            "--compress",
+            "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        let chunk = png.chunk_by_type("ruSt").unwrap();
+        assert!(chunk.data().len() < message.len());
+        let decompressed = codecs(&sub_matches(&["decode", p, "ruSt", "--decompress"]))
+            .unwrap()
+            .decode(chunk.data())
+            .unwrap();
+        assert_eq!(decompressed, message.as_bytes());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    #[test]
+    fn test_encode_compress_needs_feature() {
+        let path = temp_png("commands-compress", &testing_png());
+        let p = path.to_str().unwrap();
+        assert!(encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "hi",
+            "--compress",
+            "-y"
+        ]))
+        .is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encode_with_password() {
+        let path = temp_png("commands-password", &testing_png());
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "meet at noon",
+            "--password",
+            "pw",
+            "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        let chunk = png.chunk_by_type("ruSt").unwrap();
+        assert_ne!(chunk.data(), b"meet at noon");
+
+        let decrypt = |password: &str| {
+            codecs(&sub_matches(&["decode", p, "ruSt", "--password", password]))
+                .unwrap()
+                .decode(chunk.data())
+        };
+        assert_eq!(decrypt("pw").unwrap(), b"meet at noon");
+        assert_eq!(
+            decrypt("wrong").unwrap_err().to_string(),
+            "authentication failed"
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encode_with_emitted_key() {
+        let path = temp_png("commands-emit-key", &testing_png());
+        let p = path.to_str().unwrap();
+        let key = std::env::temp_dir().join(format!("pngme-emit-key-{}.key", std::process::id()));
+        let k = key.to_str().unwrap();
+        let out = std::env::temp_dir().join(format!("pngme-emit-key-{}.txt", std::process::id()));
+        let o = out.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "meet at noon",
+            "--password",
+            "pw",
+            "--emit-key",
+            k,
+            "-y",
+        ]))
+        .unwrap();
+        // Only the ciphertext and its 16 byte tag are stored.
+        let png = read(&path);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().length(), 12 + 16);
+        assert!(fs::read_to_string(&key).unwrap().contains("salt="));
+
+        decode(&sub_matches(&[
+            "decode",
+            p,
+            "ruSt",
+            "--password",
+            "pw",
+            "--key-file",
+            k,
+            "--output-file",
+            o,
+        ]))
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"meet at noon");
+        assert!(decode(&sub_matches(&[
+            "decode",
+            p,
+            "ruSt",
+            "--password",
+            "pw",
+            "--output-file",
+            o
+        ]))
+        .is_err());
+        assert!(args::cli()
+            .try_get_matches_from(["pngme", "decode", p, "ruSt", "--key-file", k])
+            .is_err());
+
+        for file in [&path, &key, &out] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    #[test]
+    fn test_password_needs_feature() {
+        let path = temp_png("commands-password", &testing_png());
+        let p = path.to_str().unwrap();
+        assert!(encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "hi",
+            "--password",
+            "pw",
+            "-y"
+        ]))
+        .is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_type_bytes() {
+        assert_eq!(
+            parse_type_bytes("82,117,83,116").unwrap(),
+            ChunkType::from_str("RuSt").unwrap()
+        );
+        assert_eq!(
+            parse_type_bytes(" 114, 117, 83, 116 ").unwrap().to_string(),
+            "ruSt"
+        );
+    }
+
+    #[test]
+    fn test_parse_type_bytes_rejects_bad_lists() {
+        assert!(parse_type_bytes("82,117,83,256").is_err());
+        assert!(parse_type_bytes("82,117,83,-1").is_err());
+        assert!(parse_type_bytes("82,117,83").is_err());
+        assert!(parse_type_bytes("82,117,83,116,116").is_err());
+        assert!(parse_type_bytes("82,117,83,49").is_err());
+    }
+
+    #[test]
+    fn test_remove_all_ancillary() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "hello").unwrap());
+        png.append_chunk(message_chunk("tEXt", "Comment\0hi").unwrap());
+        png.append_chunk(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+        let path = temp_png("commands-remove-ancillary", &png);
+        let p = path.to_str().unwrap();
+
+        remove(&sub_matches(&["remove", p, "@ancillary", "-y"])).unwrap();
+        let types: Vec<String> = read(&path)
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "IEND"]);
+
+        fs::write(&path, png.as_bytes()).unwrap();
+        remove(&sub_matches(&["remove", p, "*", "-y"])).unwrap();
+        assert_eq!(read(&path).chunks().len(), 2);
+
+        fs::write(&path, png.as_bytes()).unwrap();
+        remove(&sub_matches(&["remove", p, "@critical", "-y"])).unwrap();
+        assert_eq!(read(&path).chunks().len(), 2);
+
+        assert!(remove(&sub_matches(&["remove", p, "@other", "-y"])).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_remove_all_of_type() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "one").unwrap());
+        png.append_chunk(message_chunk("tEXt", "Comment\0hi").unwrap());
+        png.append_chunk(message_chunk("ruSt", "two").unwrap());
+        let path = temp_png("commands-remove-all", &png);
+        let p = path.to_str().unwrap();
+
+        assert!(remove(&sub_matches(&["remove", p, "teSt", "--all", "-y"])).is_err());
+        remove(&sub_matches(&["remove", p, "ruSt", "--all", "-y"])).unwrap();
+        let png = read(&path);
+        assert!(png.chunk_by_type("ruSt").is_none());
+        assert_eq!(png.chunks().len(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_remove_with_chunk_type_bytes() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "hello").unwrap());
+        let path = temp_png("commands-type-bytes", &png);
+        let p = path.to_str().unwrap();
+
+        remove(&sub_matches(&[
+            "remove",
+            p,
+            "--chunk-type-bytes",
+            "114,117,83,116",
+            "-y",
+        ]))
+        .unwrap();
+        assert!(read(&path).chunk_by_type("ruSt").is_none());
+
+        assert!(args::cli()
+            .try_get_matches_from([
+                "pngme",
+                "remove",
+                p,
+                "ruSt",
+                "--chunk-type-bytes",
+                "1,2,3,4"
+            ])
+            .is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_replace_or_append() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+        let path = temp_png("commands-upsert", &png);
+        let p = path.to_str().unwrap();
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "first",
+            "--replace-or-append",
+            "-y",
+        ]))
+        .unwrap();
+        let types: Vec<String> = read(&path)
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "ruSt", "IEND"]);
+
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "second",
+            "--replace-or-append",
+            "-y",
+        ]))
+        .unwrap();
+        let png = read(&path);
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(decode_message(&png, "ruSt", false).unwrap(), "second");
+        assert_eq!(
+            png.chunk_index_by_type(&ChunkType::from_str("ruSt").unwrap()),
+            Some(1)
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_as_text_requires_text_type() {
+        let path = temp_png("commands-as-text", &testing_png());
+        let p = path.to_str().unwrap();
+
+        let matches = sub_matches(&["encode", p, "ruSt", "hi", "--as-text", "Comment", "-y"]);
+        assert!(encode(&matches).is_err());
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "tEXt",
+            "hi",
+            "--as-text",
+            "Comment",
+            "-y",
+        ]))
+        .unwrap();
+        assert_eq!(
+            find_message(&read(&path), "tEXt", false).unwrap().data(),
+            b"Comment\0hi"
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_print_and_lint_commands() {
+        let path = temp_png("commands-print", &testing_png());
+        let p = path.to_str().unwrap();
+
+        print(&sub_matches(&["print", p, "--crc-hex"])).unwrap();
+        print(&sub_matches(&["print", p, "--escape"])).unwrap();
+        // No IDAT or IEND
+        assert_eq!(lint(&sub_matches(&["lint", p, "--json"])).unwrap(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_assume_yes_skips_prompt() {
+        let mut input = io::empty();
+        let mut output = Vec::new();
+        let confirmed =
+            confirm_overwrite(Path::new("a.png"), true, true, &mut input, &mut output).unwrap();
+        assert!(confirmed);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_confirm_overwrite_prompt() {
+        let mut output = Vec::new();
+        let confirmed = confirm_overwrite(
+            Path::new("a.png"),
+            false,
+            true,
+            &mut "y\n".as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+        assert!(confirmed);
+        assert_eq!(output, b"Overwrite a.png? [y/N] ");
+
+        let confirmed = confirm_overwrite(
+            Path::new("a.png"),
+            false,
+            true,
+            &mut "\n".as_bytes(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert!(!confirmed);
+    }
+
+    #[test]
+    fn test_verify_after_write() {
+        let path = temp_png("commands-verify", &testing_png());
+        let p = path.to_str().unwrap();
+        encode(&sub_matches(&[
+            "encode",
+            p,
+            "ruSt",
+            "hi",
+            "--verify-after-write",
+            "-y",
+        ]))
+        .unwrap();
+
+        let mut broken = read(&path);
+        broken.append_chunk(Chunk::builder().crc_override(0).build().unwrap());
+        let err = write_png(&path, &broken, true).unwrap_err();
+        assert!(err.to_string().contains("failed verification"));
+        write_png(&path, &broken, false).unwrap();
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_chunk_csv() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "hello").unwrap());
+        let mut out = Vec::new();
+        write_chunk_csv(&mut out, "a,b.png", png.chunks().iter().enumerate()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "file,index,type,length,crc,critical,public,safe_to_copy"
+        );
+        assert_eq!(
+            lines[2],
+            format!(
+                "\"a,b.png\",1,ruSt,5,{:08x},false,false,true",
+                png.chunks()[1].crc()
+            )
+        );
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn test_write_chunk_list() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "hello").unwrap());
+        let mut out = Vec::new();
+        write_chunk_list(&mut out, &png).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("  0  IHDR          13 bytes  crc "));
+        assert!(lines[0].ends_with("  critical"));
+        let crc = png.chunks()[1].crc();
+        assert_eq!(
+            lines[1],
+            format!("  1  ruSt           5 bytes  crc {:08x}  ancillary", crc)
+        );
+    }
+
+    #[test]
+    fn test_repair_lengths() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "hello").unwrap());
+        let mut bytes = png.as_bytes();
+        bytes[33..37].copy_from_slice(&2u32.to_be_bytes());
+        let path = std::env::temp_dir().join("pngme-commands-repair-lengths.png");
+        fs::write(&path, &bytes).unwrap();
+        let p = path.to_str().unwrap();
+
+        assert_eq!(lint(&sub_matches(&["lint", p])).unwrap(), 2);
+        repair_lengths(&sub_matches(&["repair-lengths", p, "-y"])).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+
+        // Nothing left to repair, but --output still gets a copy.
+        let out = std::env::temp_dir().join("pngme-commands-repair-lengths-out.png");
+        let o = out.to_str().unwrap();
+        repair_lengths(&sub_matches(&["repair-lengths", p, "--output", o, "-y"])).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), png.as_bytes());
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn test_change_summary() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("RuSt", "hello").unwrap());
+        let matches = sub_matches(&["encode", "img.png", "RuSt", "hello"]);
+        let what = format!(
+            "encoded 'RuSt' (5 bytes) into {}",
+            destination(Path::new("img.png"), &matches)
+        );
+        assert_eq!(
+            change_summary(&what, &png),
+            "encoded 'RuSt' (5 bytes) into img.png; file now 50 bytes, 2 chunks"
+        );
+
+        let matches = sub_matches(&["remove", "img.png", "RuSt", "-o", "-"]);
+        assert_eq!(destination(Path::new("img.png"), &matches), "stdout");
+    }
+
+    #[test]
+    fn test_write_crc_report() {
+        let mut png = testing_png();
+        png.append_chunk(message_chunk("ruSt", "hello").unwrap());
+        let mut out = Vec::new();
+        assert_eq!(write_crc_report(&mut out, &png).unwrap(), 0);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "all 2 chunk CRCs are valid\n"
+        );
+
+        png.append_chunk(
+            Chunk::builder()
+                .data_str("bad")
+                .crc_override(0xdeadbeef)
+                .build()
+                .unwrap(),
+        );
+        let mut out = Vec::new();
+        assert_eq!(write_crc_report(&mut out, &png).unwrap(), 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ruSt at offset 50: stored crc deadbeef doesn't match its data\n"
+        );
+    }
+
+    #[test]
+    fn test_verify_file_ignores_time() {
+        let with_time = |time: &str| {
+            let mut png = testing_png();
+            png.append_chunk(
+                Chunk::builder()
+                    .chunk_type("tIME")
+                    .data_str(time)
+                    .build()
+                    .unwrap(),
+            );
+            png.append_chunk(message_chunk("ruSt", "same").unwrap());
+            png
+        };
+        let path = temp_png("commands-verify-file", &with_time("2024"));
+        let reference = temp_png("commands-verify-file-ref", &with_time("2025"));
+        let (p, r) = (path.to_str().unwrap(), reference.to_str().unwrap());
+
+        let argv = ["verify-file", p, "--against", r];
+        assert_eq!(verify_file(&sub_matches(&argv)).unwrap(), 1);
+        let argv = ["verify-file", p, "--against", r, "--ignore", "tIME"];
+        assert_eq!(verify_file(&sub_matches(&argv)).unwrap(), 0);
+        let argv = ["verify-file", p, "--against", r, "--ignore", "IDAT"];
+        assert!(verify_file(&sub_matches(&argv)).is_err());
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&reference).unwrap();
+    }
+
+    #[test]
+    fn test_write_difference() {
+        let mut out = Vec::new();
+        write_difference(&mut out, None).unwrap();
+        let diff = ChunkDiff::Extra {
+            index: 3,
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+        };
+        write_difference(&mut out, Some(&diff)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "equivalent to the reference\nchunk 3 (ruSt) is not in the reference\n"
+        );
+    }
+
+    #[test]
+    fn test_write_chunk_types() {
+        let mut out = Vec::new();
+        write_chunk_types(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains(
+            "IHDR  critical  public  unsafe-to-copy Image header: dimensions, bit depth and color type"
+        ));
+        assert!(out.contains("tEXt  ancillary public  safe-to-copy   Latin-1 textual data"));
+    }
+
+    #[test]
+    fn test_write_signature() {
+        let mut bytes = testing_png().as_bytes();
+        let mut out = Vec::new();
+        write_signature(&mut out, &bytes).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Signature: 89 50 4E 47 0D 0A 1A 0A\nMatches the PNG signature\n"
+        );
+
+        bytes[1] = b'J';
+        let mut out = Vec::new();
+        write_signature(&mut out, &bytes).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Signature: 89 4A 4E 47 0D 0A 1A 0A\n\
+             Does not match the PNG signature (89 50 4E 47 0D 0A 1A 0A)\n"
+        );
+    }
+
+    #[test]
+    fn test_write_palette_chunks() {
+        let mut png = testing_png();
+        for (chunk_type, data) in [("PLTE", &[0; 6][..]), ("sBIT", &[8; 3]), ("hIST", &[0; 4])] {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str(chunk_type).unwrap(),
+                data.to_vec(),
+            ));
+        }
+        encode_message(&mut png, "sPLT", "name\0\x08").unwrap();
+
+        let mut out = Vec::new();
+        write_palette_chunks(&mut out, &png).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "PLTE: 6 bytes\nsBIT: 3 bytes\nhIST: 4 bytes\nsPLT: 6 bytes\n"
+        );
+    }
+
+    #[test]
+    fn test_check_file_size() {
+        let path = std::env::temp_dir().join("pngme-commands-max-file-size.png");
+        fs::write(&path, testing_png().as_bytes()).unwrap();
+        let p = path.to_str().unwrap();
+
+        check_file_size(&sub_matches(&["list", p])).unwrap();
+        let err = check_file_size(&sub_matches(&["list", p, "--max-file-size", "10"])).unwrap_err();
+        assert!(err.to_string().starts_with(&format!(
+            "{} is 33 bytes, over the --max-file-size limit of 10",
+            p
+        )));
+        check_file_size(&sub_matches(&[
+            "decode",
+            p,
+            "ruSt",
+            "--stream-output",
+            "--max-file-size",
+            "10",
+        ]))
+        .unwrap();
+        check_file_size(&sub_matches(&["chunk-types"])).unwrap();
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_json_output() {
+        let mut png = testing_png();
+        encode_message(&mut png, "ruSt", "say \"hi\"").unwrap();
+
+        let list = chunks_json(png.chunks().iter().enumerate(), false).unwrap();
+        assert!(list.starts_with("{\"chunks\":[{\"index\":0,\"type\":\"IHDR\",\"length\":13,"));
+        for key in [
+            "index",
+            "type",
+            "length",
+            "crc",
+            "crc_valid",
+            "critical",
+            "public",
+            "safe_to_copy",
+        ] {
+            assert!(list.contains(&format!("\"{}\":", key)), "missing {}", key);
+        }
+        assert!(!list.contains("\"data\":"));
+
+        let private = png.chunks().iter().enumerate().skip(1);
+        let print = chunks_json(private, true).unwrap();
+        assert!(print.contains("\"crc_valid\":true,\"critical\":false,\"public\":false"));
+        assert!(print.ends_with("\"data\":\"say \\\"hi\\\"\"}]}"));
+
+        let info = info_json(&png).unwrap();
+        assert!(info.starts_with("{\"width\":"));
+        for key in [
+            "height",
+            "bit_depth",
+            "color_type",
+            "interlace",
+            "total_pixels",
+        ] {
+            assert!(info.contains(&format!("\"{}\":", key)), "missing {}", key);
+        }
+
+        assert!(args::cli()
+            .try_get_matches_from(["pngme", "list", "a.png", "--csv", "--format", "json"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_write_counts() {
+        let mut png = testing_png();
+        for message in ["one", "two", "three"] {
+            encode_message(&mut png, "ruSt", message).unwrap();
+        }
+        encode_message(&mut png, "geMs", "four").unwrap();
+        let mut out = Vec::new();
+        write_counts(&mut out, &png.type_counts()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "IHDR  1\ngeMs  1\nruSt  3\n"
+        );
+    }
+
+    #[test]
+    fn test_write_profile() {
+        let histogram = vec![
+            (ChunkType::from_str("IDAT").unwrap(), 100),
+            (ChunkType::from_str("tEXt").unwrap(), 25),
+            (ChunkType::from_str("IEND").unwrap(), 0),
+        ];
+        let mut out = Vec::new();
+        write_profile(&mut out, &histogram).unwrap();
+        let lines: Vec<String> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| l.trim_end().to_string())
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                format!("IDAT  {}  100 bytes", "#".repeat(40)),
+                format!("tEXt  {:<40}  25 bytes", "#".repeat(10)),
+                format!("IEND  {:<40}  0 bytes", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_data() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let text = Chunk::new(chunk_type, b"hello".to_vec());
+        assert_eq!(render_data(&text).unwrap(), "hello");
+        let binary = Chunk::new(chunk_type, vec![0x00, 0xff, 0x10]);
+        assert_eq!(render_data(&binary).unwrap(), "0x00ff10");
+    }
+
+    #[test]
+    fn test_wrap() {
+        let message = "The quick brown fox jumps over the lazy dog, twice over.";
+        let wrapped = wrap(message, 20);
+        let lengths: Vec<usize> = wrapped.lines().map(|l| l.chars().count()).collect();
+        assert_eq!(lengths, vec![20, 20, 16]);
+        assert_eq!(wrapped.replace('\n', ""), message);
+
+        assert_eq!(wrap("héllo\n\nwörld", 3), "hél\nlo\n\nwör\nld");
+        assert_eq!(wrap("unchanged", 0), "unchanged");
+    }
+
+    #[test]
+    fn test_write_raw() {
+        let data = vec![0, 159, 146, 150, 10, 255];
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), data.clone());
+
+        let mut out = Vec::new();
+        write_raw(&mut out, &chunk).unwrap();
+
+        assert_eq!(out, data);
+    }
+}
@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+use crate::chunk::{Chunk, MAX_LENGTH};
+use crate::chunk_type::ChunkType;
+use crate::png::{Header, Png};
+use crate::Result;
+
+/// Largest amount of data a single stored deflate block can hold.
+const MAX_STORED_BLOCK: usize = 0xffff;
+
+/// Build a truecolor PNG of the given size where every pixel is `rgb`. The pixels
+/// are compressed with flate2 when that feature is on, and otherwise stored in
+/// uncompressed deflate blocks so `create` works without any dependencies.
+pub fn solid_png(width: u32, height: u32, rgb: [u8; 3]) -> Result<Png> {
+    if width == 0 || height == 0 {
+        let e: Box<dyn std::error::Error> =
+            format!("can't create a {}x{} image", width, height).into();
+        return Err(e);
+    }
+    // Checked before allocating. The stored size bounds the compressed one, and
+    // also caps how much memory the uncompressed pixels take.
+    if stored_idat_len(width, height) > MAX_LENGTH as u64 {
+        return Err(format!(
+            "a {}x{} image needs more than the {} bytes a single IDAT chunk can hold",
+            width, height, MAX_LENGTH
+        )
+        .into());
+    }
+    let header = Header {
+        width,
+        height,
+        bit_depth: 8,
+        color_type: 2,
+        compression: 0,
+        filter: 0,
+        interlace: 0,
+    };
+
+    // Each scanline is a filter type byte (0, none) followed by the pixels.
+    let mut scanline = vec![0u8];
+    for _ in 0..width {
+        scanline.extend_from_slice(&rgb);
+    }
+    let pixels = scanline.repeat(height as usize);
+
+    Ok(Png::from_chunks(vec![
+        Chunk::new(ChunkType::from_str("IHDR")?, header.to_bytes().to_vec()),
+        Chunk::new(ChunkType::from_str("IDAT")?, compress(&pixels)),
+        Chunk::new(ChunkType::from_str("IEND")?, Vec::new()),
+    ]))
+}
+
+#[cfg(feature = "flate2")]
+fn compress(pixels: &[u8]) -> Vec<u8> {
+    use crate::codec::DataCodec;
+
+    crate::codec::Deflate.encode(pixels)
+}
+
+#[cfg(not(feature = "flate2"))]
+fn compress(pixels: &[u8]) -> Vec<u8> {
+    zlib_stored(pixels)
+}
+
+/// The length of `zlib_stored` output for a `width` by `height` RGB image.
+/// Saturates rather than overflowing for the largest sizes.
+fn stored_idat_len(width: u32, height: u32) -> u64 {
+    let pixels = (1 + 3 * width as u64).saturating_mul(height as u64);
+    let blocks = pixels.div_ceil(MAX_STORED_BLOCK as u64).max(1);
+    pixels.saturating_add(2 + 5 * blocks + 4)
+}
+
+/// Wrap `data` in a zlib stream made of uncompressed ("stored") deflate blocks,
+/// which every PNG decoder has to accept.
+#[cfg_attr(feature = "flate2", allow(dead_code))]
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg_attr(feature = "flate2", allow(dead_code))]
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solid_png_round_trips() {
+        let png = solid_png(2, 2, [255, 0, 0]).unwrap();
+        let parsed = Png::try_from(png.as_bytes().as_slice()).unwrap();
+
+        let types: Vec<String> = parsed
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+
+        let header = parsed.ihdr().unwrap();
+        assert_eq!((header.width, header.height), (2, 2));
+        assert_eq!(header.color_type, 2);
+    }
+
+    #[test]
+    fn test_zlib_stored() {
+        let data = b"\0\xff\0\0\xff\0\0";
+        let stream = zlib_stored(data);
+        assert_eq!(&stream[..2], &[0x78, 0x01]);
+        assert_eq!(&stream[2..7], &[1, 7, 0, 0xf8, 0xff]);
+        assert_eq!(&stream[7..14], data);
+        assert_eq!(&stream[14..], &adler32(data).to_be_bytes());
+
+        let big = vec![0u8; MAX_STORED_BLOCK + 1];
+        let stream = zlib_stored(&big);
+        assert_eq!(stream[2], 0);
+        assert_eq!(stream.len(), 2 + 2 * 5 + big.len() + 4);
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn test_solid_png_rejects_empty_size() {
+        assert!(solid_png(0, 2, [0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_solid_png_rejects_oversized() {
+        let Err(err) = solid_png(u32::MAX, u32::MAX, [0, 0, 0]) else {
+            panic!("a 2^32 pixel square image can't fit in one IDAT");
+        };
+        assert!(err.to_string().contains("single IDAT chunk"));
+        assert!(solid_png(30_000, 30_000, [0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_stored_idat_len() {
+        let png = solid_png(3, 5, [1, 2, 3]).unwrap();
+        assert_eq!(
+            stored_idat_len(3, 5),
+            zlib_stored(&[0; 10 * 5]).len() as u64
+        );
+        let big = MAX_STORED_BLOCK as u32;
+        assert_eq!(
+            stored_idat_len(big, 1),
+            zlib_stored(&vec![0; 1 + 3 * big as usize]).len() as u64
+        );
+        assert!(png.chunk_by_type("IDAT").unwrap().length() as u64 <= stored_idat_len(3, 5));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::Result;
+
+/// Quote a CSV field if it holds a comma, quote or line break, doubling any quotes.
+pub fn field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Join `fields` into one CSV row, without the line ending.
+pub fn row(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|f| field(f))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Split CSV text into rows of fields. Fields may be wrapped in double quotes,
+/// with `""` standing for a literal quote and line breaks kept inside a quoted field.
+/// Blank lines are skipped.
+pub fn parse(input: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                line += 1;
+                end_row(&mut rows, &mut fields, &mut field);
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(format!("unterminated quote on csv line {}", line).into());
+    }
+    end_row(&mut rows, &mut fields, &mut field);
+    Ok(rows)
+}
+
+/// Finish the row in progress, dropping it if the line was blank.
+fn end_row(rows: &mut Vec<Vec<String>>, fields: &mut Vec<String>, field: &mut String) {
+    fields.push(std::mem::take(field));
+    let row = std::mem::take(fields);
+    if !(row.len() == 1 && row[0].trim().is_empty()) {
+        rows.push(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        assert_eq!(field("plain"), "plain");
+        assert_eq!(field("a,b"), "\"a,b\"");
+        assert_eq!(field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_row() {
+        assert_eq!(row(&["x.png", "0", "IHDR"]), "x.png,0,IHDR");
+        assert_eq!(row(&["a,b.png", ""]), "\"a,b.png\",");
+    }
+
+    #[test]
+    fn test_parse() {
+        let rows = parse("a,b\n\"c, \"\"d\"\"\",e\r\n\n").unwrap();
+        assert_eq!(rows, vec![vec!["a", "b"], vec!["c, \"d\"", "e"]]);
+        assert!(parse("\"open").is_err());
+    }
+
+    #[test]
+    fn test_row_parses_back() {
+        let rows = [
+            vec!["plain", "", "a,b"],
+            vec!["say \"hi\"", "two\nlines", "cr\r\nlf"],
+        ];
+        let text: String = rows.iter().map(|r| row(r) + "\n").collect();
+        assert_eq!(parse(&text).unwrap(), rows);
+    }
+}
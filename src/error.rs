@@ -0,0 +1,82 @@
+use std::fmt::Display;
+
+use crate::chunk_type::ChunkType;
+
+/// The ways parsing a PNG, a chunk or a chunk type can fail, for callers that
+/// want to tell them apart.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PngError {
+    /// The stored CRC doesn't match the one computed from the chunk type and data.
+    InvalidCrc { expected: u32, actual: u32 },
+    /// A chunk type byte isn't an ASCII letter.
+    InvalidChunkType([u8; 4]),
+    /// A chunk type wasn't exactly four bytes long.
+    ChunkTypeLength(usize),
+    /// The data doesn't start with the PNG signature.
+    MissingSignature,
+    /// Another PNG signature starts at this offset, as in PNG files joined together.
+    MultipleSignatures(usize),
+    /// No chunk of this type is present.
+    ChunkNotFound(String),
+    /// There were fewer bytes than needed to parse a chunk or signature.
+    TooShort { needed: usize, got: usize },
+    /// A chunk ended before its data or CRC did.
+    TruncatedChunk {
+        chunk_type: ChunkType,
+        part: &'static str,
+        needed: usize,
+        got: usize,
+    },
+    /// A chunk declared a length above the 2^31-1 bytes the PNG spec allows.
+    LengthTooLarge { chunk_type: ChunkType, length: u32 },
+    /// Bytes were left over after a chunk that was meant to fill its input.
+    TrailingBytes { chunk_type: ChunkType, extra: usize },
+}
+
+impl std::error::Error for PngError {}
+
+impl Display for PngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::InvalidCrc { expected, actual } => write!(
+                f,
+                "invalid crc: expected {:#010x}, found {:#010x}",
+                expected, actual
+            ),
+            PngError::InvalidChunkType(_) => write!(f, "Invalid ChunkType"),
+            PngError::ChunkTypeLength(len) => {
+                write!(f, "chunk type must be 4 bytes, got {}", len)
+            }
+            PngError::MissingSignature => write!(f, "not a PNG file: missing the PNG signature"),
+            PngError::MultipleSignatures(offset) => {
+                write!(f, "multiple PNG signatures found at offset {}", offset)
+            }
+            PngError::ChunkNotFound(chunk_type) => write!(f, "{} chunk not found", chunk_type),
+            PngError::TooShort { needed, got } => {
+                write!(f, "expected at least {} bytes, found {}", needed, got)
+            }
+            PngError::TruncatedChunk {
+                chunk_type,
+                part,
+                needed,
+                got,
+            } => write!(
+                f,
+                "chunk '{}' truncated: expected {} {} bytes, found {}",
+                chunk_type, needed, part, got
+            ),
+            PngError::LengthTooLarge { chunk_type, length } => write!(
+                f,
+                "chunk '{}' declares {} data bytes, more than the PNG limit of {}",
+                chunk_type,
+                length,
+                crate::chunk::MAX_LENGTH
+            ),
+            PngError::TrailingBytes { chunk_type, extra } => write!(
+                f,
+                "chunk '{}' followed by {} unexpected bytes",
+                chunk_type, extra
+            ),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::io::{BufRead, BufReader, Read};
+
+use crate::Result;
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Decompress `contents` if it starts with the gzip magic bytes, otherwise return it as is.
+pub fn decompress(contents: Vec<u8>) -> Result<Vec<u8>> {
+    if !is_gzip(&contents) {
+        return Ok(contents);
+    }
+    let mut decompressed = Vec::new();
+    decompress_reader(Box::new(contents.as_slice()))?.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Wrap `reader` in a gzip decoder if its data starts with the gzip magic bytes.
+pub fn decompress_reader<'a>(reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    if !is_gzip(reader.fill_buf()?) {
+        return Ok(Box::new(reader));
+    }
+    #[cfg(feature = "flate2")]
+    return Ok(Box::new(flate2::bufread::GzDecoder::new(reader)));
+    #[cfg(not(feature = "flate2"))]
+    Err("input is gzip-compressed; build pngme with the flate2 feature to read it".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_input_is_unchanged() {
+        let contents = b"\x89PNG\r\n\x1a\n".to_vec();
+        assert_eq!(decompress(contents.clone()).unwrap(), contents);
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    #[test]
+    fn test_gzip_needs_feature() {
+        assert!(decompress(vec![0x1f, 0x8b, 8, 0]).is_err());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_decompress_gzipped_png() {
+        use std::io::Write;
+
+        use crate::png::Png;
+
+        let png_bytes = std::fs::read("example.png").unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&png_bytes).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        assert!(is_gzip(&gzipped));
+
+        let png = Png::try_from(decompress(gzipped).unwrap().as_slice()).unwrap();
+        let expected = Png::try_from(png_bytes.as_slice()).unwrap();
+        assert_eq!(png.chunks(), expected.chunks());
+        assert!(png.chunk_by_type("ruSt").is_some());
+    }
+}
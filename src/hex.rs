@@ -0,0 +1,80 @@
+use crate::Result;
+
+/// Format bytes as lowercase hex digit pairs, like `89504e47`.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parse a string of hex digit pairs, like `89504e47`, into bytes.
+pub fn decode(s: &str) -> Result<Vec<u8>> {
+    // Checked up front so the error can say where the bad digit is; from_str_radix
+    // alone would also accept a sign, as in "+1".
+    if let Some((offset, c)) = s.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex digit {:?} at offset {}", c, offset).into());
+    }
+    if !s.len().is_multiple_of(2) {
+        return Err(format!(
+            "hex data must have an even number of digits, got {}",
+            s.len()
+        )
+        .into());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&s[i..i + 2], 16)?))
+        .collect()
+}
+
+/// Like `decode`, but skipping any whitespace between the digits, as in `8950 4e47`.
+pub fn decode_spaced(s: &str) -> Result<Vec<u8>> {
+    let digits: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    decode(&digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(&[0x00, 0xff, 0x7a]), "00ff7a");
+        assert_eq!(decode(&encode(b"round trip")).unwrap(), b"round trip");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("00ff7A").unwrap(), vec![0x00, 0xff, 0x7a]);
+        assert_eq!(decode("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_decode_spaced() {
+        assert_eq!(
+            decode_spaced("00 ff\n7A\t").unwrap(),
+            vec![0x00, 0xff, 0x7a]
+        );
+        assert!(decode_spaced("0 0f")
+            .unwrap_err()
+            .to_string()
+            .contains("even number"));
+    }
+
+    #[test]
+    fn test_decode_odd_length() {
+        let err = decode("abc").unwrap_err();
+        assert!(err.to_string().contains("even number"));
+    }
+
+    #[test]
+    fn test_decode_non_hex() {
+        assert_eq!(
+            decode("zz").unwrap_err().to_string(),
+            "invalid hex digit 'z' at offset 0"
+        );
+        assert_eq!(
+            decode("00ff\n7a").unwrap_err().to_string(),
+            r"invalid hex digit '\n' at offset 4"
+        );
+        assert!(decode("+1").is_err());
+    }
+}
@@ -0,0 +1,27 @@
+pub mod args;
+pub mod batch;
+pub mod browse;
+pub mod chunk;
+pub mod chunk_type;
+#[cfg(feature = "encryption")]
+pub mod cipher;
+pub mod codec;
+pub mod commands;
+pub mod create;
+mod csv;
+pub mod error;
+mod gzip;
+mod hex;
+mod json;
+pub mod lint;
+pub mod pipe;
+pub mod png;
+
+pub use chunk::Chunk;
+pub use chunk_type::ChunkType;
+pub use commands::{decode_message, encode_message, parse_chunk_type, remove_message};
+pub use error::PngError;
+pub use png::Png;
+
+pub type Error = Box<dyn std::error::Error>;
+pub type Result<T> = std::result::Result<T, Error>;
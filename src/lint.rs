@@ -0,0 +1,425 @@
+use std::fmt::Display;
+
+use crate::json;
+use crate::png::{LengthMismatch, Png};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    /// Process exit code used when this is the highest severity found.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Severity::Info => 0,
+            Severity::Warning => 1,
+            Severity::Error => 2,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub chunk_index: Option<usize>,
+}
+
+impl Finding {
+    fn new(
+        code: &'static str,
+        severity: Severity,
+        chunk_index: Option<usize>,
+        message: impl Into<String>,
+    ) -> Finding {
+        Finding {
+            code,
+            severity,
+            message: message.into(),
+            chunk_index,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let chunk_index = match self.chunk_index {
+            Some(i) => i.to_string(),
+            None => String::from("null"),
+        };
+        format!(
+            "{{\"code\":{},\"severity\":{},\"message\":{},\"chunk_index\":{}}}",
+            json::quote(self.code),
+            json::quote(self.severity.as_str()),
+            json::quote(&self.message),
+            chunk_index
+        )
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]", self.severity, self.code)?;
+        if let Some(i) = self.chunk_index {
+            write!(f, " chunk {}", i)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Check the chunk layout of a PNG against the structural rules of the spec.
+pub fn lint(png: &Png) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let chunks = png.chunks();
+    let position = |name: &str| {
+        chunks
+            .iter()
+            .position(|c| c.chunk_type().bytes() == name.as_bytes())
+    };
+
+    match position("IHDR") {
+        Some(0) => {}
+        Some(i) => findings.push(Finding::new(
+            "E001",
+            Severity::Error,
+            Some(i),
+            "IHDR must be the first chunk",
+        )),
+        None => findings.push(Finding::new(
+            "E001",
+            Severity::Error,
+            None,
+            "missing IHDR chunk",
+        )),
+    }
+
+    match position("IEND") {
+        Some(i) if i == chunks.len() - 1 => {}
+        Some(i) => findings.push(Finding::new(
+            "E002",
+            Severity::Error,
+            Some(i),
+            "IEND must be the last chunk",
+        )),
+        None => findings.push(Finding::new(
+            "E002",
+            Severity::Error,
+            None,
+            "missing IEND chunk",
+        )),
+    }
+
+    if position("IDAT").is_none() {
+        findings.push(Finding::new(
+            "E003",
+            Severity::Error,
+            None,
+            "missing IDAT chunk",
+        ));
+    }
+
+    lint_palette_chunks(png, &mut findings);
+
+    let mut ihdr_seen = false;
+    for (i, chunk) in chunks.iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        if chunk_type.bytes() == *b"IHDR" && std::mem::replace(&mut ihdr_seen, true) {
+            findings.push(Finding::new(
+                "E004",
+                Severity::Error,
+                Some(i),
+                "duplicate IHDR chunk",
+            ));
+        }
+        if !chunk_type.is_reserved_bit_valid() {
+            findings.push(Finding::new(
+                "W001",
+                Severity::Warning,
+                Some(i),
+                format!("chunk type '{}' has the reserved bit set", chunk_type),
+            ));
+        }
+        if !chunk_type.is_public() {
+            findings.push(Finding::new(
+                "I001",
+                Severity::Info,
+                Some(i),
+                format!("private chunk type '{}'", chunk_type),
+            ));
+        }
+    }
+
+    findings
+}
+
+/// Report chunks whose declared length disagrees with their CRC. These come from the raw
+/// bytes, since such a PNG doesn't parse until `Png::repair_lengths` has fixed it.
+pub fn length_findings(mismatches: &[LengthMismatch]) -> Vec<Finding> {
+    mismatches
+        .iter()
+        .map(|m| {
+            Finding::new(
+                "E006",
+                Severity::Error,
+                Some(m.index),
+                format!(
+                    "{} declares {} data bytes but its CRC matches {}",
+                    m.chunk_type, m.declared, m.actual
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Check `sBIT` and `hIST` against the color type and palette they describe.
+fn lint_palette_chunks(png: &Png, findings: &mut Vec<Finding>) {
+    let chunks = png.chunks();
+    let plte = chunks.iter().find(|c| c.chunk_type().bytes() == *b"PLTE");
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        match &chunk.chunk_type().bytes() {
+            b"hIST" => match plte {
+                None => findings.push(Finding::new(
+                    "E005",
+                    Severity::Error,
+                    Some(i),
+                    "hIST chunk requires a PLTE chunk",
+                )),
+                // One 2 byte frequency per 3 byte palette entry.
+                Some(plte) if chunk.data().len() != plte.data().len() / 3 * 2 => {
+                    findings.push(Finding::new(
+                        "W003",
+                        Severity::Warning,
+                        Some(i),
+                        format!(
+                            "hIST has {} bytes but the palette has {} entries",
+                            chunk.data().len(),
+                            plte.data().len() / 3
+                        ),
+                    ))
+                }
+                Some(_) => {}
+            },
+            b"sBIT" => {
+                // Indexed-color images give significant bits for the RGB palette samples.
+                let expected = png.ihdr().ok().and_then(|h| match h.color_type {
+                    3 => Some(3),
+                    _ => h.channels(),
+                });
+                if let Some(expected) = expected {
+                    if chunk.data().len() != expected as usize {
+                        findings.push(Finding::new(
+                            "W002",
+                            Severity::Warning,
+                            Some(i),
+                            format!(
+                                "sBIT has {} bytes but the color type needs {}",
+                                chunk.data().len(),
+                                expected
+                            ),
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The exit code for a set of findings, driven by the highest severity.
+pub fn exit_code(findings: &[Finding]) -> i32 {
+    findings
+        .iter()
+        .map(|f| f.severity)
+        .max()
+        .map_or(0, |s| s.exit_code())
+}
+
+pub fn findings_to_json(findings: &[Finding]) -> String {
+    let items: Vec<String> = findings.iter().map(|f| f.to_json()).collect();
+    format!(
+        "{{\"findings\":[{}],\"exit_code\":{}}}",
+        items.join(","),
+        exit_code(findings)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_lint_clean_png() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        let findings = lint(&png);
+        assert!(findings.is_empty());
+        assert_eq!(exit_code(&findings), 0);
+    }
+
+    #[test]
+    fn test_lint_ihdr_out_of_place_or_duplicated() {
+        let png = Png::from_chunks(vec![
+            chunk("IDAT", &[]),
+            chunk("IHDR", &[0; 13]),
+            chunk("IEND", &[]),
+        ]);
+        let codes: Vec<&str> = lint(&png).iter().map(|f| f.code).collect();
+        assert_eq!(codes, ["E001"]);
+
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", &[]),
+            chunk("IHDR", &[0; 13]),
+            chunk("IEND", &[]),
+        ]);
+        let findings = lint(&png);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            (findings[0].code, findings[0].chunk_index),
+            ("E004", Some(2))
+        );
+    }
+
+    #[test]
+    fn test_lint_json_and_exit_code() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", &[]),
+            chunk("Rust", b"hi"),
+        ]);
+        let findings = lint(&png);
+        assert_eq!(exit_code(&findings), 2);
+        assert_eq!(
+            findings_to_json(&findings),
+            concat!(
+                "{\"findings\":[",
+                "{\"code\":\"E002\",\"severity\":\"error\",\"message\":\"missing IEND chunk\",\"chunk_index\":null},",
+                "{\"code\":\"W001\",\"severity\":\"warning\",\"message\":\"chunk type 'Rust' has the reserved bit set\",\"chunk_index\":2},",
+                "{\"code\":\"I001\",\"severity\":\"info\",\"message\":\"private chunk type 'Rust'\",\"chunk_index\":2}",
+                "],\"exit_code\":2}"
+            )
+        );
+    }
+
+    fn ihdr(color_type: u8) -> Chunk {
+        chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, color_type, 0, 0, 0])
+    }
+
+    fn codes(findings: &[Finding]) -> Vec<&'static str> {
+        findings.iter().map(|f| f.code).collect()
+    }
+
+    #[test]
+    fn test_lint_palette_chunks() {
+        let png = Png::from_chunks(vec![
+            ihdr(3),
+            chunk("sBIT", &[8, 8, 8]),
+            chunk("PLTE", &[0; 6]),
+            chunk("hIST", &[0; 4]),
+            chunk("sPLT", b"name\0\x08"),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        assert!(lint(&png).is_empty());
+    }
+
+    #[test]
+    fn test_lint_hist_requires_plte() {
+        let png = Png::from_chunks(vec![
+            ihdr(2),
+            chunk("hIST", &[0; 4]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        assert_eq!(codes(&lint(&png)), vec!["E005"]);
+    }
+
+    #[test]
+    fn test_lint_palette_chunk_lengths() {
+        let png = Png::from_chunks(vec![
+            ihdr(6),
+            chunk("sBIT", &[8, 8, 8]),
+            chunk("PLTE", &[0; 6]),
+            chunk("hIST", &[0; 6]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        let findings = lint(&png);
+        assert_eq!(codes(&findings), vec!["W002", "W003"]);
+        assert_eq!(
+            findings[0].message,
+            "sBIT has 3 bytes but the color type needs 4"
+        );
+    }
+
+    #[test]
+    fn test_lint_info_exit_code() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("ruSt", b"hi"),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        let findings = lint(&png);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(exit_code(&findings), 0);
+    }
+
+    #[test]
+    fn test_lint_warning_exit_code() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("Rust", b"hi"),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        assert_eq!(exit_code(&lint(&png)), 1);
+    }
+
+    #[test]
+    fn test_length_findings() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", b"image data"),
+            chunk("IEND", &[]),
+        ]);
+        let mut bytes = png.as_bytes();
+        bytes[8 + 25..8 + 29].copy_from_slice(&3u32.to_be_bytes());
+
+        let findings = length_findings(&Png::find_length_mismatches(&bytes));
+        assert_eq!(codes(&findings), vec!["E006"]);
+        assert_eq!(findings[0].chunk_index, Some(1));
+        assert_eq!(
+            findings[0].message,
+            "IDAT declares 3 data bytes but its CRC matches 10"
+        );
+        assert_eq!(exit_code(&findings), 2);
+    }
+}
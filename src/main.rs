@@ -1,97 +1,38 @@
-use std::fs;
-use std::path::PathBuf;
-use std::str::FromStr;
-
-use chunk::Chunk;
-use chunk_type::ChunkType;
-
-use crate::png::Png;
-
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod png;
-
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+use pngme::{args, commands, Result};
 
 fn main() -> Result<()> {
     let matches = args::cli().get_matches();
+    if let Some((_, sub_matches)) = matches.subcommand() {
+        commands::check_file_size(sub_matches)?;
+    }
 
     match matches.subcommand() {
-        Some(("encode", sub_matches)) => {
-            let path = PathBuf::from_str(sub_matches.get_one::<String>("PATH").expect("required"))?;
-
-            let contents = fs::read(&path)?;
-            let mut png = Png::try_from(contents.as_slice())?;
-
-            let chunk_type = ChunkType::from_str(
-                sub_matches
-                    .get_one::<String>("CHUNK_TYPE")
-                    .expect("required"),
-            )?;
-            let message = sub_matches.get_one::<String>("MESSAGE").expect("required");
-            let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
-
-            png.append_chunk(chunk);
-
-            fs::write(&path, png.as_bytes())?;
-
-            Ok(())
-        }
-        Some(("decode", sub_matches)) => {
-            let path = PathBuf::from_str(sub_matches.get_one::<String>("PATH").expect("required"))?;
-            let contents = fs::read(&path)?;
-            let png = Png::try_from(contents.as_slice())?;
-
-            let chunk_type = ChunkType::from_str(
-                sub_matches
-                    .get_one::<String>("CHUNK_TYPE")
-                    .expect("required"),
-            )?;
-
-            let e: Box<dyn std::error::Error> = String::from("no message found").into();
-            let message = png
-                .chunks()
-                .iter()
-                .find(|c| c.chunk_type() == &chunk_type)
-                .ok_or(e)?
-                .data_as_string()?;
-
-            println!("Message: {}", message);
-            Ok(())
-        }
-        Some(("remove", sub_matches)) => {
-            let path = PathBuf::from_str(sub_matches.get_one::<String>("PATH").expect("required"))?;
-            let contents = fs::read(&path)?;
-            let mut png = Png::try_from(contents.as_slice())?;
-
-            let chunk_type = sub_matches
-                .get_one::<String>("CHUNK_TYPE")
-                .expect("required");
-
-            png.remove_chunk(chunk_type)?;
-
-            fs::write(&path, png.as_bytes())?;
-
-            Ok(())
-        }
-        Some(("print", sub_matches)) => {
-            let path = PathBuf::from_str(sub_matches.get_one::<String>("PATH").expect("required"))?;
-            let contents = fs::read(&path)?;
-            let png = Png::try_from(contents.as_slice())?;
-
-            let chunks: Vec<&Chunk> = png
-                .chunks()
-                .iter()
-                .filter(|c| !c.chunk_type().is_public())
-                .collect();
-
-            println!("Chunks: {:#?}", chunks);
-
-            Ok(())
+        Some(("encode", sub_matches)) => commands::encode(sub_matches),
+        Some(("create", sub_matches)) => commands::create(sub_matches),
+        Some(("encode-batch", sub_matches)) => commands::encode_batch(sub_matches),
+        Some(("decode", sub_matches)) => commands::decode(sub_matches),
+        Some(("has", sub_matches)) => std::process::exit(commands::has(sub_matches)?),
+        Some(("remove", sub_matches)) => commands::remove(sub_matches),
+        Some(("print", sub_matches)) => commands::print(sub_matches),
+        Some(("move-chunk", sub_matches)) => commands::move_chunk(sub_matches),
+        Some(("strip-before-idat", sub_matches)) => commands::strip_before_idat(sub_matches),
+        Some(("minify", sub_matches)) => commands::minify(sub_matches),
+        Some(("split", sub_matches)) => commands::split(sub_matches),
+        Some(("dedup", sub_matches)) => commands::dedup(sub_matches),
+        Some(("pipe", sub_matches)) => commands::pipe(sub_matches),
+        Some(("info", sub_matches)) => commands::info(sub_matches),
+        Some(("browse", sub_matches)) => commands::browse(sub_matches),
+        Some(("profile", sub_matches)) => commands::profile(sub_matches),
+        Some(("count", sub_matches)) => commands::count(sub_matches),
+        Some(("dump-signature", sub_matches)) => commands::dump_signature(sub_matches),
+        Some(("verify-file", sub_matches)) => {
+            std::process::exit(commands::verify_file(sub_matches)?)
         }
+        Some(("repair-lengths", sub_matches)) => commands::repair_lengths(sub_matches),
+        Some(("verify", sub_matches)) => std::process::exit(commands::verify(sub_matches)?),
+        Some(("list", sub_matches)) => commands::list(sub_matches),
+        Some(("chunk-types", sub_matches)) => commands::chunk_types(sub_matches),
+        Some(("lint", sub_matches)) => std::process::exit(commands::lint(sub_matches)?),
         _ => Ok(()),
     }
 }
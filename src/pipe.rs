@@ -0,0 +1,143 @@
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::Result;
+
+/// A single step of a `pipe` invocation, parsed from specs like `encode:ruSt:hi`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Remove every ancillary chunk.
+    Strip,
+    /// Append a chunk of the given type holding the message.
+    Encode(ChunkType, String),
+    /// Remove the first chunk of the given type.
+    Remove(String),
+    /// Replace any `pHYs` chunk with one for the given dots per inch.
+    SetDpi(u32),
+}
+
+impl Op {
+    pub fn apply(&self, png: &mut Png) -> Result<()> {
+        match self {
+            Op::Strip => {
+                png.retain_chunks(|c| c.chunk_type().is_critical());
+            }
+            Op::Encode(chunk_type, message) => {
+                png.append_chunk(Chunk::new(*chunk_type, message.as_bytes().to_vec()));
+            }
+            Op::Remove(chunk_type) => {
+                png.remove_chunk(chunk_type)?;
+            }
+            Op::SetDpi(dpi) => {
+                if png.chunks().first().map(|c| c.chunk_type().bytes()) != Some(*b"IHDR") {
+                    let e: Box<dyn std::error::Error> =
+                        String::from("set-dpi requires IHDR to be the first chunk").into();
+                    return Err(e);
+                }
+                png.retain_chunks(|c| c.chunk_type().bytes() != *b"pHYs");
+
+                // pHYs stores pixels per metre, with unit 1 meaning metres.
+                let ppm = (*dpi as f64 / 0.0254).round() as u32;
+                let data: Vec<u8> = ppm
+                    .to_be_bytes()
+                    .iter()
+                    .chain(ppm.to_be_bytes().iter())
+                    .chain([1].iter())
+                    .copied()
+                    .collect();
+                png.insert_after_ihdr(Chunk::new(ChunkType::from_str("pHYs")?, data))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Op {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let op = match (parts.next(), parts.next(), parts.next()) {
+            (Some("strip"), None, None) => Op::Strip,
+            (Some("encode"), Some(chunk_type), Some(message)) => {
+                Op::Encode(ChunkType::from_str(chunk_type)?, message.to_string())
+            }
+            (Some("remove"), Some(chunk_type), None) => Op::Remove(chunk_type.to_string()),
+            (Some("set-dpi"), Some(dpi), None) => Op::SetDpi(dpi.parse()?),
+            _ => return Err(format!("invalid operation '{}'", s).into()),
+        };
+        Ok(op)
+    }
+}
+
+/// Apply each operation to `png` in order, stopping at the first failure.
+pub fn run(png: &mut Png, ops: &[Op]) -> Result<()> {
+    for op in ops.iter() {
+        op.apply(png)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("tEXt", b"Comment\0hi"),
+            chunk("IDAT", &[]),
+        ])
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_ops() {
+        assert_eq!(Op::from_str("strip").unwrap(), Op::Strip);
+        assert_eq!(
+            Op::from_str("encode:ruSt:a:b").unwrap(),
+            Op::Encode(ChunkType::from_str("ruSt").unwrap(), String::from("a:b"))
+        );
+        assert_eq!(Op::from_str("set-dpi:300").unwrap(), Op::SetDpi(300));
+        assert!(Op::from_str("strip:now").is_err());
+        assert!(Op::from_str("set-dpi:lots").is_err());
+    }
+
+    #[test]
+    fn test_strip_then_encode() {
+        let mut png = testing_png();
+        let ops = vec![
+            Op::from_str("strip").unwrap(),
+            Op::from_str("encode:ruSt:hi").unwrap(),
+        ];
+        run(&mut png, &ops).unwrap();
+
+        assert_eq!(types(&png), vec!["IHDR", "IDAT", "ruSt"]);
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(),
+            "hi"
+        );
+    }
+
+    #[test]
+    fn test_set_dpi() {
+        let mut png = testing_png();
+        run(&mut png, &[Op::SetDpi(300), Op::SetDpi(72)]).unwrap();
+
+        assert_eq!(types(&png), vec!["IHDR", "pHYs", "tEXt", "IDAT"]);
+        let data = png.chunk_by_type("pHYs").unwrap().data();
+        assert_eq!(data, &[0, 0, 11, 19, 0, 0, 11, 19, 1]);
+    }
+}
@@ -1,10 +1,14 @@
 #![allow(dead_code)]
+use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{self, Read, Write};
 use std::str::FromStr;
 
+use crc::{Crc, CRC_32_ISO_HDLC};
+
 use crate::chunk::Chunk;
 use crate::chunk_type::ChunkType;
+use crate::error::PngError;
 use crate::Result;
 
 pub struct Png {
@@ -13,7 +17,7 @@ pub struct Png {
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
     pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Png {
@@ -22,17 +26,277 @@ impl Png {
         }
     }
 
+    /// Add `chunk` at the end, but before a trailing `IEND` so the PNG stays valid.
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk)
+        if self.has_iend() {
+            self.chunks.insert(self.chunks.len() - 1, chunk);
+        } else {
+            self.chunks.push(chunk);
+        }
+    }
+
+    /// Whether the last chunk is `IEND`, as it must be in a complete PNG.
+    pub fn has_iend(&self) -> bool {
+        self.chunks
+            .last()
+            .is_some_and(|c| c.chunk_type().bytes() == *b"IEND")
+    }
+
+    /// Append a zero-length `IEND` chunk unless the PNG already ends with one.
+    pub fn ensure_iend(&mut self) {
+        if !self.has_iend() {
+            self.chunks
+                .push(Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()));
+        }
+    }
+
+    /// Insert `chunk` just before `IEND`, or at the end if there is no `IEND`.
+    pub fn append_before_iend(&mut self, chunk: Chunk) {
+        match self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().bytes() == *b"IEND")
+        {
+            Some(i) => self.chunks.insert(i, chunk),
+            None => self.chunks.push(chunk),
+        }
+    }
+
+    /// Swap `chunk` in for the first chunk of the same type, keeping its position,
+    /// and return the chunk it replaced.
+    pub fn replace_chunk(&mut self, chunk: Chunk) -> Result<Chunk> {
+        let chunk_type = chunk.chunk_type().to_string();
+        let slot = self
+            .chunk_by_type_mut(&chunk_type)
+            .ok_or(PngError::ChunkNotFound(chunk_type))?;
+        Ok(std::mem::replace(slot, chunk))
+    }
+
+    /// Insert `chunk` directly after `IHDR`, where chunks like `gAMA` and `pHYs` belong.
+    pub fn insert_after_ihdr(&mut self, chunk: Chunk) -> Result<()> {
+        let ihdr = self
+            .chunk_index_by_type(&ChunkType::try_from(*b"IHDR")?)
+            .ok_or_else(|| PngError::ChunkNotFound(String::from("IHDR")))?;
+        self.chunks.insert(ihdr + 1, chunk);
+        Ok(())
+    }
+
+    /// Insert `chunk` so it ends up at `index`, which may be the end of the PNG
+    /// but not past a trailing `IEND`.
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        let last = if self.has_iend() {
+            self.chunks.len() - 1
+        } else {
+            self.chunks.len()
+        };
+        if index > last {
+            return Err(format!(
+                "can't insert a chunk at index {}: the last allowed index is {}",
+                index, last
+            )
+            .into());
+        }
+        self.chunks.insert(index, chunk);
+        Ok(())
     }
 
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
-        let e: Box<dyn std::error::Error> = String::from("chunk not found").into();
         self.chunks
             .iter()
             .position(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
             .map(|i| self.chunks.remove(i))
-            .ok_or(e)
+            .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()).into())
+    }
+
+    /// Remove every chunk of `chunk_type` and return them in their original order.
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        let (removed, kept) = self
+            .chunks
+            .drain(..)
+            .partition(|c| c.chunk_type().bytes() == chunk_type.as_bytes());
+        self.chunks = kept;
+        removed
+    }
+
+    /// Keep only the chunks for which `f` returns true, returning how many were removed.
+    /// The chunks that are kept stay in their original relative order.
+    pub fn retain_chunks<F: FnMut(&Chunk) -> bool>(&mut self, f: F) -> usize {
+        let before = self.chunks.len();
+        self.chunks.retain(f);
+        before - self.chunks.len()
+    }
+
+    /// Remove the chunks for which `f` returns true, returning how many were removed.
+    /// Like `retain_chunks`, the rest stay in their original relative order.
+    pub fn remove_chunks<F: FnMut(&Chunk) -> bool>(&mut self, mut f: F) -> usize {
+        self.retain_chunks(|c| !f(c))
+    }
+
+    /// Remove ancillary chunks that come before the first `IDAT`, leaving any after it.
+    pub fn remove_ancillary_before_idat(&mut self) -> usize {
+        let first_idat = match self.first_idat_index() {
+            Some(i) => i,
+            None => return 0,
+        };
+        let mut index = 0;
+        self.retain_chunks(|c| {
+            let keep = index >= first_idat || c.chunk_type().is_critical();
+            index += 1;
+            keep
+        })
+    }
+
+    /// Keep only critical chunks, dropping all metadata, returning how many were removed.
+    pub fn keep_only_critical(&mut self) -> usize {
+        self.retain_chunks(|c| c.chunk_type().is_critical())
+    }
+
+    /// Remove chunks that are byte-for-byte equal to an earlier chunk, returning how many were removed.
+    /// The first of each set of duplicates is kept, and the survivors keep their relative order.
+    pub fn dedup_chunks(&mut self) -> usize {
+        let before = self.chunks.len();
+        let mut kept: Vec<Chunk> = Vec::with_capacity(before);
+        for chunk in self.chunks.drain(..) {
+            if !kept.contains(&chunk) {
+                kept.push(chunk);
+            }
+        }
+        self.chunks = kept;
+        before - self.chunks.len()
+    }
+
+    /// Replace every chunk with the result of `f`, keeping their order.
+    pub fn map_chunks<F: FnMut(Chunk) -> Chunk>(&mut self, f: F) {
+        self.chunks = self.chunks.drain(..).map(f).collect();
+    }
+
+    pub fn swap_chunks(&mut self, i: usize, j: usize) -> Result<()> {
+        if i >= self.chunks.len() || j >= self.chunks.len() {
+            let e: Box<dyn std::error::Error> = String::from("chunk index out of bounds").into();
+            return Err(e);
+        }
+        self.chunks.swap(i, j);
+        Ok(())
+    }
+
+    pub fn move_chunk(&mut self, from: usize, to: usize) -> Result<()> {
+        if from >= self.chunks.len() || to >= self.chunks.len() {
+            let e: Box<dyn std::error::Error> = String::from("chunk index out of bounds").into();
+            return Err(e);
+        }
+        let chunk = self.chunks.remove(from);
+        self.chunks.insert(to, chunk);
+        Ok(())
+    }
+
+    /// The image header parsed from the `IHDR` chunk.
+    pub fn ihdr(&self) -> Result<Header> {
+        let chunk = self
+            .chunk_by_type("IHDR")
+            .ok_or_else(|| PngError::ChunkNotFound(String::from("IHDR")))?;
+        Header::try_from(chunk.data())
+    }
+
+    /// Width × height, or `None` when there is no valid `IHDR`.
+    pub fn total_pixels(&self) -> Option<u64> {
+        let header = self.ihdr().ok()?;
+        (header.width as u64).checked_mul(header.height as u64)
+    }
+
+    /// Bytes needed to hold the decoded image, rounded up to whole bytes.
+    pub fn estimated_decoded_size(&self) -> Option<u64> {
+        let header = self.ihdr().ok()?;
+        let bits = self
+            .total_pixels()?
+            .checked_mul(header.bits_per_pixel()? as u64)?;
+        Some(bits.div_ceil(8))
+    }
+
+    /// The first place where `self` and `other` differ chunk by chunk, comparing type
+    /// and data and skipping chunks of the `ignore` types in both. `None` means equivalent.
+    pub fn first_difference(&self, other: &Png, ignore: &[ChunkType]) -> Option<ChunkDiff> {
+        let (ours, theirs) = (self.indexed_except(ignore), other.indexed_except(ignore));
+        for i in 0..ours.len().max(theirs.len()) {
+            match (ours.get(i), theirs.get(i)) {
+                (Some(&(index, ours)), Some(&(other_index, theirs))) => {
+                    if !ours.content_eq(theirs) {
+                        return Some(ChunkDiff::Changed {
+                            index,
+                            other_index,
+                            chunk_type: *ours.chunk_type(),
+                            other_type: *theirs.chunk_type(),
+                        });
+                    }
+                }
+                (Some(&(index, chunk)), None) => {
+                    return Some(ChunkDiff::Extra {
+                        index,
+                        chunk_type: *chunk.chunk_type(),
+                    })
+                }
+                (None, Some(&(other_index, chunk))) => {
+                    return Some(ChunkDiff::Missing {
+                        other_index,
+                        chunk_type: *chunk.chunk_type(),
+                    })
+                }
+                (None, None) => unreachable!("i is below the longer length"),
+            }
+        }
+        None
+    }
+
+    /// The chunks not of the `ignore` types, with their indexes.
+    fn indexed_except(&self, ignore: &[ChunkType]) -> Vec<(usize, &Chunk)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !ignore.contains(c.chunk_type()))
+            .collect()
+    }
+
+    /// Total data bytes per chunk type, largest first. Types with equal totals
+    /// keep the order they first appear in.
+    pub fn size_histogram(&self) -> Vec<(ChunkType, u64)> {
+        let mut histogram: Vec<(ChunkType, u64)> = Vec::new();
+        for chunk in self.chunks.iter() {
+            let size = chunk.data().len() as u64;
+            match histogram.iter_mut().find(|(t, _)| t == chunk.chunk_type()) {
+                Some((_, total)) => *total += size,
+                None => histogram.push((*chunk.chunk_type(), size)),
+            }
+        }
+        histogram.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        histogram
+    }
+
+    /// The number of chunks of each type, keyed by type name so they come out sorted.
+    pub fn type_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for chunk in self.chunks.iter() {
+            *counts.entry(chunk.chunk_type().to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Scan a PNG stream chunk by chunk for the first chunk where `f` returns true,
+    /// stopping there without reading or parsing the rest of the stream.
+    pub fn find_in_stream<R: Read, F: FnMut(&Chunk) -> bool>(
+        mut reader: R,
+        mut f: F,
+    ) -> Result<Option<Chunk>> {
+        let mut header: [u8; 8] = [0; 8];
+        reader.read_exact(&mut header)?;
+        if header != Png::STANDARD_HEADER {
+            return Err(PngError::MissingSignature.into());
+        }
+
+        while let Some(chunk) = Chunk::from_reader(&mut reader)? {
+            if f(&chunk) {
+                return Ok(Some(chunk));
+            }
+        }
+        Ok(None)
     }
 
     pub fn header(&self) -> &[u8; 8] {
@@ -43,6 +307,20 @@ impl Png {
         &self.chunks
     }
 
+    /// The chunks, for editing in place. `Chunk::set_data` keeps each chunk's length and
+    /// CRC in step with its data; `Chunk::refresh_crc` repairs a stale CRC.
+    pub fn chunks_mut(&mut self) -> &mut [Chunk] {
+        &mut self.chunks
+    }
+
+    /// Iterate over the chunks along with where each starts in `as_bytes`.
+    pub fn iter(&self) -> ChunkIter<'_> {
+        ChunkIter {
+            chunks: self.chunks.iter(),
+            offset: self.header.len(),
+        }
+    }
+
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
         let target = match ChunkType::from_str(chunk_type) {
             Ok(c) => c,
@@ -51,36 +329,278 @@ impl Png {
         self.chunks.iter().find(|c| c.chunk_type() == &target)
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let chunk_bytes: Vec<u8> = self.chunks.iter().flat_map(|c| c.as_bytes()).collect();
-        self.header
+    /// Every chunk of `chunk_type`, in file order.
+    pub fn chunks_by_type(&self, chunk_type: &ChunkType) -> Vec<&Chunk> {
+        self.chunks
             .iter()
-            .chain(chunk_bytes.iter())
-            .copied()
+            .filter(|c| c.chunk_type() == chunk_type)
             .collect()
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = crate::Error;
+    /// The first chunk, in file order, whose type is any of `types`.
+    pub fn first_chunk_of_types<'a>(&'a self, types: &[ChunkType]) -> Option<&'a Chunk> {
+        self.chunks.iter().find(|c| types.contains(c.chunk_type()))
+    }
 
-    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
-        let mut reader = BufReader::new(value);
-        let mut header: [u8; 8] = [0; 8];
-        reader.read_exact(&mut header)?;
+    pub fn contains_chunk_type(&self, chunk_type: &ChunkType) -> bool {
+        self.chunks.iter().any(|c| c.chunk_type() == chunk_type)
+    }
 
-        if header != Png::STANDARD_HEADER {
-            let e: Box<dyn std::error::Error> = String::from("Invalid header").into();
-            return Err(e);
+    /// The index of the first `IDAT` chunk, where the image data starts.
+    pub fn first_idat_index(&self) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|c| c.chunk_type().bytes() == *b"IDAT")
+    }
+
+    /// The index of the first chunk of `chunk_type`.
+    pub fn chunk_index_by_type(&self, chunk_type: &ChunkType) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|c| c.chunk_type() == chunk_type)
+    }
+
+    /// The index of every chunk of `chunk_type`, in order.
+    pub fn all_indices_by_type(&self, chunk_type: &ChunkType) -> Vec<usize> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.chunk_type() == chunk_type)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn chunk_by_type_mut(&mut self, chunk_type: &str) -> Option<&mut Chunk> {
+        let target = match ChunkType::from_str(chunk_type) {
+            Ok(c) => c,
+            _ => return None,
+        };
+        self.chunks.iter_mut().find(|c| c.chunk_type() == &target)
+    }
+
+    /// Size of the PNG when serialized, signature included. Fails rather than
+    /// wrapping when the size doesn't fit in a `usize`, as can happen on 32-bit targets.
+    pub fn total_len(&self) -> Result<usize> {
+        checked_total(
+            self.header.len(),
+            self.chunks.iter().map(Chunk::len_on_disk),
+        )
+    }
+
+    /// Like `total_len`, but saturating at `usize::MAX`.
+    pub fn len_on_disk(&self) -> usize {
+        self.total_len().unwrap_or(usize::MAX)
+    }
+
+    /// Like `try_from`, but keeps chunks whose stored CRC doesn't match their data
+    /// instead of failing, so writing the PNG back reproduces those CRCs.
+    pub fn try_from_lenient(value: &[u8]) -> std::result::Result<Png, PngError> {
+        Png::parse_whole(value, false)
+    }
+
+    /// Where the PNG signature starts in `value`, looking no further than `limit` bytes
+    /// in, for recovering files with junk written before the PNG.
+    pub fn signature_offset(value: &[u8], limit: usize) -> Option<usize> {
+        value
+            .windows(Png::STANDARD_HEADER.len())
+            .take(limit.saturating_add(1))
+            .position(|w| w == Png::STANDARD_HEADER)
+    }
+
+    /// Parse as much of `value` as possible for recovering a damaged PNG. Chunks with a
+    /// bad CRC or type are skipped, and parsing stops at a truncated chunk or another
+    /// PNG signature; each problem is recorded in the returned errors.
+    pub fn salvage(value: &[u8]) -> (Png, Vec<PngError>) {
+        let mut errors = Vec::new();
+        if Png::STANDARD_HEADER.starts_with(value) && value.len() < 8 {
+            let e = PngError::TooShort {
+                needed: 8,
+                got: value.len(),
+            };
+            return (Png::from_chunks(Vec::new()), vec![e]);
         }
+        if !value.starts_with(&Png::STANDARD_HEADER) {
+            // Carry on as if the signature were intact.
+            errors.push(PngError::MissingSignature);
+        }
+
+        let mut chunks = Vec::new();
+        let mut start: usize = 8;
+        while start < value.len() {
+            let rest = &value[start..];
+            if rest.starts_with(&Png::STANDARD_HEADER) {
+                errors.push(PngError::MultipleSignatures(start));
+                break;
+            }
+            match Chunk::try_from_with_remainder(rest) {
+                Ok((chunk, _)) => {
+                    start += chunk.len_on_disk();
+                    chunks.push(chunk);
+                }
+                Err(e @ (PngError::InvalidCrc { .. } | PngError::InvalidChunkType(_))) => {
+                    // The length field is intact, so skip to the next chunk.
+                    let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+                    errors.push(e);
+                    // Saturating, as a garbage length can overflow on 32-bit targets.
+                    start = start.saturating_add(length as usize).saturating_add(12);
+                }
+                Err(e) => {
+                    errors.push(e);
+                    break;
+                }
+            }
+        }
+        (Png::from_chunks(chunks), errors)
+    }
+
+    /// Scan the bytes of a PNG for chunks whose declared length is wrong, recognized by
+    /// the stored CRC matching some other length that is followed by another chunk or
+    /// the end of the file. Scanning stops at a chunk that can't be explained either way.
+    pub fn find_length_mismatches(value: &[u8]) -> Vec<LengthMismatch> {
+        let mut mismatches = Vec::new();
+        if !value.starts_with(&Png::STANDARD_HEADER) {
+            return mismatches;
+        }
+        let mut start = Png::STANDARD_HEADER.len();
+        let mut index = 0;
+        while value.len() - start >= 12 {
+            let rest = &value[start..];
+            let Ok(chunk_type) = ChunkType::try_from(&rest[4..8]) else {
+                break;
+            };
+            let len = match Chunk::try_from_with_remainder(rest) {
+                Ok((chunk, _)) => chunk.data().len(),
+                Err(_) => match crc_matching_length(rest) {
+                    Some(len) => {
+                        mismatches.push(LengthMismatch {
+                            index,
+                            offset: start,
+                            chunk_type,
+                            declared: u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]),
+                            actual: len as u32,
+                        });
+                        len
+                    }
+                    None => break,
+                },
+            };
+            start += len + 12;
+            index += 1;
+        }
+        mismatches
+    }
+
+    /// Correct the length fields found by `find_length_mismatches`, returning the
+    /// repaired bytes along with the mismatches fixed.
+    pub fn repair_lengths(value: &[u8]) -> (Vec<u8>, Vec<LengthMismatch>) {
+        let mismatches = Png::find_length_mismatches(value);
+        let mut repaired = value.to_vec();
+        for mismatch in &mismatches {
+            repaired[mismatch.offset..mismatch.offset + 4]
+                .copy_from_slice(&mismatch.actual.to_be_bytes());
+        }
+        (repaired, mismatches)
+    }
+
+    fn parse_whole(value: &[u8], verify_crc: bool) -> std::result::Result<Png, PngError> {
+        let (png, end) = Png::parse_prefix(value, verify_crc)?;
+        if end < value.len() {
+            return Err(PngError::MultipleSignatures(end));
+        }
+        Ok(png)
+    }
+
+    /// Split the bytes of several PNG files joined end to end into one `Png` each.
+    pub fn split_concatenated(value: &[u8]) -> Result<Vec<Png>> {
+        let mut pngs = Vec::new();
+        let mut start = 0;
+        while start < value.len() {
+            let (png, len) = Png::parse_prefix(&value[start..], true)?;
+            pngs.push(png);
+            start += len;
+        }
+        Ok(pngs)
+    }
+
+    /// Parse a PNG from the start of `value`, stopping at the end or where another
+    /// PNG signature begins, and return it along with the number of bytes it used.
+    fn parse_prefix(value: &[u8], verify_crc: bool) -> std::result::Result<(Png, usize), PngError> {
+        if !value.starts_with(&Png::STANDARD_HEADER) {
+            // A cut-off signature is a truncated PNG; anything else isn't a PNG at all.
+            if Png::STANDARD_HEADER.starts_with(value) {
+                return Err(PngError::TooShort {
+                    needed: 8,
+                    got: value.len(),
+                });
+            }
+            return Err(PngError::MissingSignature);
+        }
+        let header = Png::STANDARD_HEADER;
 
         let mut chunks: Vec<Chunk> = Vec::new();
-        let mut start: u32 = 8;
-        while let Ok(chunk) = Chunk::try_from(&value[start as usize..]) {
-            start += chunk.length() + 12; // Size of length: u32 + chunk_type: [u8; 4] + crc: u32
+        let mut start: usize = 8;
+        while start < value.len() && !value[start..].starts_with(&Png::STANDARD_HEADER) {
+            let (chunk, _) = Chunk::parse(&value[start..], verify_crc)?;
+            start += chunk.length() as usize + 12; // Size of length: u32 + chunk_type: [u8; 4] + crc: u32
             chunks.push(chunk);
         }
-        Ok(Png { header, chunks })
+        Ok((Png { header, chunks }, start))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        // Too big to pre-size means too big to build; let the pushes fail instead.
+        let mut bytes = Vec::with_capacity(self.total_len().unwrap_or(0));
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Write the signature and each chunk straight to `w`, without building the
+    /// whole file in memory like `as_bytes` does.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.header)?;
+        for chunk in &self.chunks {
+            chunk.write_to(w)?;
+        }
+        Ok(())
+    }
+}
+
+/// The data length at which the chunk starting at `rest` has a matching CRC, followed
+/// by the end of the bytes or something that looks like another chunk.
+fn crc_matching_length(rest: &[u8]) -> Option<usize> {
+    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    let mut digest = crc.digest();
+    digest.update(&rest[4..8]);
+    for len in 0..=rest.len() - 12 {
+        if len > 0 {
+            digest.update(&rest[7 + len..8 + len]);
+        }
+        let end = 8 + len;
+        let stored = u32::from_be_bytes([rest[end], rest[end + 1], rest[end + 2], rest[end + 3]]);
+        let next = &rest[end + 4..];
+        let plausible =
+            next.is_empty() || (next.len() >= 12 && ChunkType::try_from(&next[4..8]).is_ok());
+        if plausible && digest.clone().finalize() == stored {
+            return Some(len);
+        }
+    }
+    None
+}
+
+/// Add `sizes` to `start`, failing instead of overflowing.
+fn checked_total<I: IntoIterator<Item = usize>>(start: usize, sizes: I) -> Result<usize> {
+    sizes
+        .into_iter()
+        .try_fold(start, |total, size| total.checked_add(size))
+        .ok_or_else(|| String::from("PNG size overflows usize").into())
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        Png::parse_whole(value, true)
     }
 }
 
@@ -96,22 +616,135 @@ impl Display for Png {
     }
 }
 
+/// Yields `(offset, chunk)` pairs; see `Png::iter`.
+pub struct ChunkIter<'a> {
+    chunks: std::slice::Iter<'a, Chunk>,
+    offset: usize,
+}
+
+impl<'a> Iterator for ChunkIter<'a> {
+    type Item = (usize, &'a Chunk);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunks.next()?;
+        let offset = self.offset;
+        self.offset = self.offset.saturating_add(chunk.len_on_disk());
+        Some((offset, chunk))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+/// A chunk whose declared length disagrees with the data its CRC covers; see
+/// `Png::find_length_mismatches`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LengthMismatch {
+    pub index: usize,
+    /// Where the chunk's length field starts.
+    pub offset: usize,
+    pub chunk_type: ChunkType,
+    pub declared: u32,
+    /// The length the stored CRC matches.
+    pub actual: u32,
+}
+
+/// Where `Png::first_difference` found two PNGs to part ways. Indexes count every
+/// chunk in their PNG, ignored ones included.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkDiff {
+    /// The chunks at these positions differ in type or data.
+    Changed {
+        index: usize,
+        other_index: usize,
+        chunk_type: ChunkType,
+        other_type: ChunkType,
+    },
+    /// Only `self` has a chunk here.
+    Extra { index: usize, chunk_type: ChunkType },
+    /// Only `other` has a chunk here.
+    Missing {
+        other_index: usize,
+        chunk_type: ChunkType,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression: u8,
+    pub filter: u8,
+    pub interlace: u8,
+}
+
+impl Header {
+    /// Samples per pixel for the color type, or `None` for an unknown type.
+    pub fn channels(&self) -> Option<u8> {
+        match self.color_type {
+            0 => Some(1), // Grayscale
+            2 => Some(3), // Truecolor
+            3 => Some(1), // Indexed-color
+            4 => Some(2), // Grayscale with alpha
+            6 => Some(4), // Truecolor with alpha
+            _ => None,
+        }
+    }
+
+    pub fn bits_per_pixel(&self) -> Option<u32> {
+        Some(self.channels()? as u32 * self.bit_depth as u32)
+    }
+
+    /// The 13 bytes of `IHDR` data describing this header.
+    pub fn to_bytes(&self) -> [u8; 13] {
+        let mut bytes = [0; 13];
+        bytes[0..4].copy_from_slice(&self.width.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.height.to_be_bytes());
+        bytes[8] = self.bit_depth;
+        bytes[9] = self.color_type;
+        bytes[10] = self.compression;
+        bytes[11] = self.filter;
+        bytes[12] = self.interlace;
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Header {
+    type Error = crate::Error;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        if value.len() != 13 {
+            let e: Box<dyn std::error::Error> = String::from("invalid IHDR length").into();
+            return Err(e);
+        }
+        Ok(Header {
+            width: u32::from_be_bytes(value[0..4].try_into()?),
+            height: u32::from_be_bytes(value[4..8].try_into()?),
+            bit_depth: value[8],
+            color_type: value[9],
+            compression: value[10],
+            filter: value[11],
+            interlace: value[12],
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
     use std::convert::TryFrom;
-    use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -169,7 +802,189 @@ mod tests {
 
         let png = Png::try_from(bytes.as_ref());
 
-        assert!(png.is_err());
+        assert!(matches!(png, Err(PngError::MissingSignature)));
+    }
+
+    #[test]
+    fn test_try_from_lenient() {
+        let mut bytes = PNG_FILE.to_vec();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(matches!(
+            Png::try_from(bytes.as_slice()),
+            Err(PngError::InvalidCrc { .. })
+        ));
+        let png = Png::try_from_lenient(&bytes).unwrap();
+        assert!(!png.chunks().last().unwrap().is_crc_valid());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    /// `testing_png` with the declared length of its second chunk, `miDl`, set to `length`.
+    fn with_second_length(length: u32) -> Vec<u8> {
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        let offset = 8 + png.chunks()[0].len_on_disk();
+        bytes[offset..offset + 4].copy_from_slice(&length.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_find_length_mismatches() {
+        assert!(Png::find_length_mismatches(&testing_png().as_bytes()).is_empty());
+
+        for declared in [5, 30, 1000] {
+            let bytes = with_second_length(declared);
+            assert!(Png::try_from(bytes.as_slice()).is_err());
+            assert_eq!(
+                Png::find_length_mismatches(&bytes),
+                vec![LengthMismatch {
+                    index: 1,
+                    offset: 8 + testing_png().chunks()[0].len_on_disk(),
+                    chunk_type: ChunkType::from_str("miDl").unwrap(),
+                    declared,
+                    actual: 18,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_repair_lengths() {
+        let (repaired, fixed) = Png::repair_lengths(&with_second_length(30));
+        assert_eq!(fixed.len(), 1);
+        assert_eq!(repaired, testing_png().as_bytes());
+
+        // A bad CRC can't be explained by a different length.
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert_eq!(Png::repair_lengths(&bytes), (bytes.clone(), vec![]));
+    }
+
+    #[test]
+    fn test_chunks_mut() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let mut png = Png::try_from_lenient(&bytes).unwrap();
+        assert!(!png.chunks()[2].is_crc_valid());
+
+        for chunk in png.chunks_mut() {
+            chunk.refresh_crc();
+        }
+        assert!(png.chunks().iter().all(Chunk::is_crc_valid));
+        assert_eq!(png.as_bytes(), testing_png().as_bytes());
+
+        png.chunks_mut()[0].set_data(b"edited".to_vec());
+        assert_eq!(png.chunks()[0].length(), 6);
+        assert!(Png::try_from(png.as_bytes().as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_write_to() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut out = Vec::new();
+        png.write_to(&mut out).unwrap();
+        assert_eq!(out, PNG_FILE);
+    }
+
+    #[test]
+    fn test_iter_offsets() {
+        let png = testing_png();
+        let offsets: Vec<usize> = png.iter().map(|(offset, _)| offset).collect();
+        assert_eq!(offsets, vec![8, 8 + 12 + 20, 8 + 12 + 20 + 12 + 18]);
+
+        let bytes = png.as_bytes();
+        for (offset, chunk) in png.iter() {
+            assert_eq!(
+                &bytes[offset..offset + chunk.len_on_disk()],
+                chunk.as_bytes()
+            );
+        }
+        assert_eq!(png.iter().count(), png.chunks().len());
+    }
+
+    #[test]
+    fn test_signature_offset() {
+        assert_eq!(Png::signature_offset(&PNG_FILE, 0), Some(0));
+
+        let garbage: Vec<u8> = (0..16).chain(PNG_FILE).collect();
+        assert_eq!(Png::signature_offset(&garbage, 1024), Some(16));
+        assert_eq!(Png::signature_offset(&garbage, 16), Some(16));
+        assert_eq!(Png::signature_offset(&garbage, 15), None);
+        assert!(Png::try_from(&garbage[16..]).is_ok());
+        assert_eq!(Png::signature_offset(b"short", 1024), None);
+    }
+
+    #[test]
+    fn test_salvage() {
+        let (png, errors) = Png::salvage(&PNG_FILE);
+        assert!(errors.is_empty());
+        assert_eq!(png.as_bytes(), PNG_FILE);
+
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        let end_of_second = 8 + png.chunks()[0].len_on_disk() + png.chunks()[1].len_on_disk();
+        bytes[end_of_second - 1] ^= 0xff;
+        bytes.extend_from_slice(&[0, 0, 0, 5, b'r', b'u']);
+
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+        let (png, errors) = Png::salvage(&bytes);
+        assert_eq!(types(&png), vec!["FrSt", "LASt"]);
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], PngError::InvalidCrc { .. }));
+        assert_eq!(errors[1], PngError::TooShort { needed: 12, got: 6 });
+    }
+
+    #[test]
+    fn test_salvage_skips_huge_garbage_length() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, b'1', b'2', b'3', b'4']);
+        bytes.extend_from_slice(&[0; 4]);
+
+        let (png, errors) = Png::salvage(&bytes);
+        assert_eq!(types(&png), vec!["FrSt", "miDl", "LASt"]);
+        assert_eq!(errors, vec![PngError::InvalidChunkType(*b"1234")]);
+    }
+
+    #[test]
+    fn test_salvage_damaged_signature() {
+        let mut bytes = testing_png().as_bytes();
+        bytes[1] = b'X';
+        let (png, errors) = Png::salvage(&bytes);
+        assert_eq!(types(&png), vec!["FrSt", "miDl", "LASt"]);
+        assert_eq!(errors, vec![PngError::MissingSignature]);
+
+        let (png, errors) = Png::salvage(&PNG_FILE[..3]);
+        assert!(png.chunks().is_empty());
+        assert_eq!(errors, vec![PngError::TooShort { needed: 8, got: 3 }]);
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let err = Png::try_from(&PNG_FILE[..5]).err().unwrap();
+        assert_eq!(err, PngError::TooShort { needed: 8, got: 5 });
+        assert!(matches!(
+            Png::try_from(&[][..]),
+            Err(PngError::TooShort { needed: 8, got: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_wrong_magic_byte() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[1] = b'Q';
+        let err = Png::try_from(bytes.as_slice()).err().unwrap();
+        assert_eq!(err, PngError::MissingSignature);
+        assert_eq!(err.to_string(), "not a PNG file: missing the PNG signature");
+
+        // A JPEG starts with FF D8 FF.
+        let jpeg = [0xff, 0xd8, 0xff, 0xe0, 0, 16, b'J', b'F', b'I', b'F'];
+        assert!(matches!(
+            Png::try_from(&jpeg[..]),
+            Err(PngError::MissingSignature)
+        ));
     }
 
     #[test]
@@ -194,6 +1009,49 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_truncated_idat() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut offset = 8;
+        for chunk in png.chunks() {
+            if chunk.chunk_type().to_string() == "IDAT" {
+                break;
+            }
+            offset += chunk.length() as usize + 12;
+        }
+        let idat_length = png.chunk_by_type("IDAT").unwrap().length();
+
+        let truncated = &PNG_FILE[..offset + 8 + 100];
+        let err = Png::try_from(truncated).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "chunk 'IDAT' truncated: expected {} data bytes, found 100",
+                idat_length
+            )
+        );
+    }
+
+    #[test]
+    fn test_find_in_stream() {
+        let mut bytes = testing_png().as_bytes();
+        let end_of_middle = bytes.len() - testing_chunks()[2].as_bytes().len();
+        // Anything after the match must not be read, so garbage there is fine.
+        bytes.truncate(end_of_middle);
+        bytes.extend_from_slice(b"not a chunk");
+        let mut reader = std::io::Cursor::new(bytes);
+
+        let chunk = Png::find_in_stream(&mut reader, |c| c.chunk_type().to_string() == "miDl")
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "I am another chunk");
+        assert_eq!(reader.position(), end_of_middle as u64);
+
+        let bytes = testing_png().as_bytes();
+        let found = Png::find_in_stream(bytes.as_slice(), |c| c.chunk_type().to_string() == "NoNe");
+        assert!(found.unwrap().is_none());
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -209,6 +1067,39 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
     }
 
+    #[test]
+    fn test_chunk_indices_by_type() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "one").unwrap(),
+            chunk_from_strings("tEXt", "Comment\0hi").unwrap(),
+            chunk_from_strings("IDAT", "two").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let idat = ChunkType::from_str("IDAT").unwrap();
+        let missing = ChunkType::from_str("ruSt").unwrap();
+
+        assert_eq!(png.chunk_index_by_type(&idat), Some(1));
+        assert_eq!(png.all_indices_by_type(&idat), vec![1, 3]);
+        assert_eq!(png.chunk_index_by_type(&missing), None);
+        assert!(png.all_indices_by_type(&missing).is_empty());
+    }
+
+    #[test]
+    fn test_first_idat_index() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("gAMA", "gamma").unwrap(),
+            chunk_from_strings("tEXt", "Comment\0hi").unwrap(),
+            chunk_from_strings("IDAT", "one").unwrap(),
+            chunk_from_strings("tIME", "later").unwrap(),
+            chunk_from_strings("IDAT", "two").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        assert_eq!(png.first_idat_index(), Some(3));
+        assert_eq!(testing_png().first_idat_index(), None);
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -218,6 +1109,28 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_insert_after_ihdr() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "image").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        png.insert_after_ihdr(chunk_from_strings("gAMA", "gamma").unwrap())
+            .unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "gAMA", "IDAT", "IEND"]);
+
+        let mut png = testing_png();
+        assert!(png
+            .insert_after_ihdr(chunk_from_strings("gAMA", "gamma").unwrap())
+            .is_err());
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -227,6 +1140,499 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_all_chunks() {
+        let mut png = testing_png();
+        assert!(png.remove_all_chunks("TeSt").is_empty());
+        assert_eq!(png.chunks().len(), 3);
+
+        let removed = png.remove_all_chunks("miDl");
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].data(), b"I am another chunk");
+        assert_eq!(types(&png), vec!["FrSt", "LASt"]);
+
+        png.append_chunk(chunk_from_strings("TeSt", "one").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "two").unwrap());
+        let removed = png.remove_all_chunks("TeSt");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].data(), b"one");
+        assert_eq!(removed[1].data(), b"two");
+        assert_eq!(types(&png), vec!["FrSt", "LASt"]);
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_ensure_iend() {
+        let mut png = testing_png();
+        assert!(!png.has_iend());
+        png.ensure_iend();
+        assert!(png.has_iend());
+        assert_eq!(types(&png), vec!["FrSt", "miDl", "LASt", "IEND"]);
+        assert_eq!(png.chunks().last().unwrap().length(), 0);
+
+        png.ensure_iend();
+        assert_eq!(types(&png), vec!["FrSt", "miDl", "LASt", "IEND"]);
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let old = png
+            .replace_chunk(chunk_from_strings("miDl", "I replaced it").unwrap())
+            .unwrap();
+        assert_eq!(old.data_as_string().unwrap(), "I am another chunk");
+        assert_eq!(types(&png), vec!["FrSt", "miDl", "LASt"]);
+        assert_eq!(
+            png.chunk_by_type("miDl").unwrap().data_as_string().unwrap(),
+            "I replaced it"
+        );
+
+        assert!(png
+            .replace_chunk(chunk_from_strings("ruSt", "new").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_append_before_iend() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        png.append_before_iend(chunk_from_strings("ruSt", "hi").unwrap());
+        assert_eq!(types(&png), vec!["IHDR", "ruSt", "IEND"]);
+
+        let mut png = testing_png();
+        png.append_before_iend(chunk_from_strings("ruSt", "hi").unwrap());
+        assert_eq!(types(&png), vec!["FrSt", "miDl", "LASt", "ruSt"]);
+    }
+
+    #[test]
+    fn test_retain_chunks() {
+        let mut png = testing_png();
+        let removed = png.retain_chunks(|c| c.chunk_type().is_critical());
+        assert_eq!(removed, 1);
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.chunk_by_type("miDl").is_none());
+    }
+
+    #[test]
+    fn test_filtering_keeps_order() {
+        let chunks = || {
+            [
+                ("IHDR", "header"),
+                ("teXt", "one"),
+                ("IDAT", "first"),
+                ("ruSt", "two"),
+                ("IDAT", "second"),
+                ("teXt", "one"),
+                ("IEND", ""),
+            ]
+            .iter()
+            .map(|(t, d)| chunk_from_strings(t, d).unwrap())
+            .collect()
+        };
+
+        let mut png = Png::from_chunks(chunks());
+        assert_eq!(png.retain_chunks(|c| c.chunk_type().is_critical()), 3);
+        assert_eq!(types(&png), vec!["IHDR", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(png.chunks()[1].data(), b"first");
+        assert_eq!(png.chunks()[2].data(), b"second");
+
+        let mut png = Png::from_chunks(chunks());
+        assert_eq!(
+            png.remove_chunks(|c| c.chunk_type().to_string() == "IDAT"),
+            2
+        );
+        assert_eq!(types(&png), vec!["IHDR", "teXt", "ruSt", "teXt", "IEND"]);
+
+        let mut png = Png::from_chunks(chunks());
+        assert_eq!(png.dedup_chunks(), 1);
+        assert_eq!(
+            types(&png),
+            vec!["IHDR", "teXt", "IDAT", "ruSt", "IDAT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_remove_ancillary_before_idat() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("teXt", "early").unwrap(),
+            chunk_from_strings("IDAT", "image").unwrap(),
+            chunk_from_strings("ruSt", "late").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        assert_eq!(png.remove_ancillary_before_idat(), 1);
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "ruSt", "IEND"]);
+
+        let mut png = testing_png();
+        assert_eq!(png.remove_ancillary_before_idat(), 0);
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0one").unwrap());
+        png.append_chunk(chunk_from_strings("ruSt", "between").unwrap());
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0two").unwrap());
+
+        let texts: Vec<String> = png
+            .chunks_by_type(&ChunkType::from_str("tEXt").unwrap())
+            .iter()
+            .map(|c| c.data_as_string().unwrap())
+            .collect();
+        assert_eq!(texts, vec!["Comment\0one", "Comment\0two"]);
+        assert!(png
+            .chunks_by_type(&ChunkType::from_str("zTXt").unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_first_chunk_of_types() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("gAMA", "gamma").unwrap(),
+            chunk_from_strings("IDAT", "image").unwrap(),
+        ]);
+        let color_types = [
+            ChunkType::from_str("sRGB").unwrap(),
+            ChunkType::from_str("gAMA").unwrap(),
+        ];
+        let found = png.first_chunk_of_types(&color_types).unwrap();
+        assert_eq!(found.chunk_type().to_string(), "gAMA");
+
+        assert!(png
+            .first_chunk_of_types(&[ChunkType::from_str("iCCP").unwrap()])
+            .is_none());
+        assert!(png.first_chunk_of_types(&[]).is_none());
+    }
+
+    #[test]
+    fn test_contains_chunk_type() {
+        let png = testing_png();
+        assert!(png.contains_chunk_type(&ChunkType::from_str("miDl").unwrap()));
+        assert!(!png.contains_chunk_type(&ChunkType::from_str("ruSt").unwrap()));
+    }
+
+    #[test]
+    fn test_total_len() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.total_len().unwrap(), PNG_FILE.len());
+
+        assert_eq!(checked_total(8, [12, 20]).unwrap(), 40);
+        assert_eq!(
+            checked_total(usize::MAX - 24, [12, 12]).unwrap(),
+            usize::MAX
+        );
+        let err = checked_total(usize::MAX - 20, [12, 12]).unwrap_err();
+        assert_eq!(err.to_string(), "PNG size overflows usize");
+    }
+
+    #[test]
+    fn test_concatenated_pngs() {
+        let first = Png::try_from(&PNG_FILE[..]).unwrap();
+        let second = testing_png();
+        let mut bytes = first.as_bytes();
+        bytes.extend(second.as_bytes());
+
+        let err = Png::try_from(bytes.as_slice()).err().unwrap();
+        assert_eq!(err, PngError::MultipleSignatures(PNG_FILE.len()));
+        assert_eq!(
+            err.to_string(),
+            format!("multiple PNG signatures found at offset {}", PNG_FILE.len())
+        );
+
+        let pngs = Png::split_concatenated(&bytes).unwrap();
+        assert_eq!(pngs.len(), 2);
+        assert_eq!(pngs[0].as_bytes(), first.as_bytes());
+        assert_eq!(pngs[1].as_bytes(), second.as_bytes());
+    }
+
+    #[test]
+    fn test_append_chunk_keeps_iend_last() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("ruSt", "hidden").unwrap());
+        assert!(png.has_iend());
+        assert_eq!(types(&png)[png.chunks().len() - 2], "ruSt");
+
+        let image = image::load_from_memory(&png.as_bytes()).unwrap();
+        assert_eq!((image.width(), image.height()), (50, 50));
+
+        // Without an IEND there is nothing to keep last.
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("ruSt", "hidden").unwrap());
+        assert_eq!(types(&png).last().unwrap(), "ruSt");
+    }
+
+    #[test]
+    fn test_ihdr() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(
+            png.ihdr().unwrap(),
+            Header {
+                width: 50,
+                height: 50,
+                bit_depth: 8,
+                color_type: 6,
+                compression: 0,
+                filter: 0,
+                interlace: 0,
+            }
+        );
+        assert!(testing_png().ihdr().is_err());
+    }
+
+    #[test]
+    fn test_first_difference() {
+        let with_time = |time: &str| {
+            let mut chunks = vec![chunk_from_strings("tIME", time).unwrap()];
+            chunks.extend(testing_chunks());
+            Png::from_chunks(chunks)
+        };
+        let (a, b) = (with_time("2024"), with_time("2025"));
+        let time = ChunkType::from_str("tIME").unwrap();
+
+        assert_eq!(a.first_difference(&a, &[]), None);
+        assert_eq!(
+            a.first_difference(&b, &[]),
+            Some(ChunkDiff::Changed {
+                index: 0,
+                other_index: 0,
+                chunk_type: time,
+                other_type: time,
+            })
+        );
+        assert_eq!(a.first_difference(&b, &[time]), None);
+        assert_eq!(
+            a.first_difference(&testing_png(), &[]),
+            Some(ChunkDiff::Changed {
+                index: 0,
+                other_index: 0,
+                chunk_type: time,
+                other_type: ChunkType::from_str("FrSt").unwrap(),
+            })
+        );
+        assert_eq!(a.first_difference(&testing_png(), &[time]), None);
+
+        let mut longer = testing_png();
+        longer.append_chunk(chunk_from_strings("ruSt", "extra").unwrap());
+        assert_eq!(
+            longer.first_difference(&a, &[time]),
+            Some(ChunkDiff::Extra {
+                index: 3,
+                chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            })
+        );
+        assert_eq!(
+            a.first_difference(&longer, &[time]),
+            Some(ChunkDiff::Missing {
+                other_index: 3,
+                chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_keep_only_critical() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_before_iend(chunk_from_strings("tEXt", "Comment\0hi").unwrap());
+        let before = png.chunks().len();
+
+        let removed = png.keep_only_critical();
+        assert!(removed >= 1);
+        assert_eq!(png.chunks().len(), before - removed);
+        assert!(png.chunks().iter().all(|c| c.chunk_type().is_critical()));
+
+        assert!(png.ihdr().is_ok());
+        assert!(png.has_iend());
+        assert!(png.first_idat_index().is_some());
+        assert!(Png::try_from(png.as_bytes().as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_dedup_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0hi").unwrap());
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0bye").unwrap());
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0hi").unwrap());
+
+        assert_eq!(png.dedup_chunks(), 1);
+        let texts: Vec<String> = png
+            .chunks()
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == "tEXt")
+            .map(|c| c.data_as_string().unwrap())
+            .collect();
+        assert_eq!(texts, vec!["Comment\0hi", "Comment\0bye"]);
+        assert_eq!(png.dedup_chunks(), 0);
+    }
+
+    #[test]
+    fn test_map_chunks() {
+        let mut png = testing_png();
+        png.map_chunks(|c| Chunk::new(*c.chunk_type(), c.data().to_ascii_uppercase()));
+
+        assert_eq!(
+            png.chunk_by_type("miDl").unwrap().data_as_string().unwrap(),
+            "I AM ANOTHER CHUNK"
+        );
+        // Chunk::new recomputed the CRCs, so the bytes parse back cleanly.
+        let reparsed = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert_eq!(reparsed.chunks(), png.chunks());
+    }
+
+    #[test]
+    fn test_swap_chunks() {
+        let mut png = testing_png();
+        png.swap_chunks(0, 2).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["LASt", "miDl", "FrSt"]);
+        assert!(png.swap_chunks(0, 3).is_err());
+    }
+
+    #[test]
+    fn test_move_chunk() {
+        let mut png = testing_png();
+        png.move_chunk(0, 2).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["miDl", "LASt", "FrSt"]);
+        assert!(png.move_chunk(3, 0).is_err());
+    }
+
+    fn png_with_ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Png {
+        let data: Vec<u8> = width
+            .to_be_bytes()
+            .iter()
+            .chain(height.to_be_bytes().iter())
+            .chain([bit_depth, color_type, 0, 0, 0].iter())
+            .copied()
+            .collect();
+        Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)])
+    }
+
+    #[test]
+    fn test_total_pixels_and_decoded_size() {
+        // 8-bit RGBA: 4 bytes per pixel
+        let png = png_with_ihdr(100, 50, 8, 6);
+        assert_eq!(png.total_pixels(), Some(5000));
+        assert_eq!(png.estimated_decoded_size(), Some(20000));
+
+        // 1-bit grayscale rounds up to whole bytes
+        let png = png_with_ihdr(3, 3, 1, 0);
+        assert_eq!(png.total_pixels(), Some(9));
+        assert_eq!(png.estimated_decoded_size(), Some(2));
+
+        // 16-bit RGBA at the maximum dimensions doesn't overflow silently
+        let png = png_with_ihdr(u32::MAX, u32::MAX, 16, 6);
+        assert_eq!(png.total_pixels(), Some(u32::MAX as u64 * u32::MAX as u64));
+        assert_eq!(png.estimated_decoded_size(), None);
+    }
+
+    #[test]
+    fn test_total_pixels_without_ihdr() {
+        let png = testing_png();
+        assert_eq!(png.total_pixels(), None);
+        assert_eq!(png.estimated_decoded_size(), None);
+    }
+
+    #[test]
+    fn test_size_histogram() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("tEXt", "Comment\0short").unwrap(),
+            chunk_from_strings("IDAT", "image data").unwrap(),
+            chunk_from_strings("IDAT", "more image data").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let histogram: Vec<(String, u64)> = png
+            .size_histogram()
+            .into_iter()
+            .map(|(t, size)| (t.to_string(), size))
+            .collect();
+        assert_eq!(
+            histogram,
+            vec![
+                (String::from("IDAT"), 25),
+                (String::from("tEXt"), 13),
+                (String::from("IHDR"), 6),
+                (String::from("IEND"), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = testing_png();
+        png.insert_chunk(0, chunk_from_strings("FrOn", "front").unwrap())
+            .unwrap();
+        png.insert_chunk(2, chunk_from_strings("MiDd", "middle").unwrap())
+            .unwrap();
+        png.insert_chunk(5, chunk_from_strings("LaSt", "last").unwrap())
+            .unwrap();
+        assert_eq!(
+            types(&png),
+            ["FrOn", "FrSt", "MiDd", "miDl", "LASt", "LaSt"]
+        );
+
+        let err = png
+            .insert_chunk(7, chunk_from_strings("OuTs", "out").unwrap())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "can't insert a chunk at index 7: the last allowed index is 6"
+        );
+
+        png.ensure_iend();
+        assert!(png
+            .insert_chunk(7, chunk_from_strings("AfTr", "after").unwrap())
+            .is_err());
+        png.insert_chunk(6, chunk_from_strings("BeFr", "before").unwrap())
+            .unwrap();
+        assert_eq!(types(&png)[6..], ["BeFr", "IEND"]);
+    }
+
+    #[test]
+    fn test_type_counts() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("ruSt", "first").unwrap(),
+            chunk_from_strings("IDAT", "image data").unwrap(),
+            chunk_from_strings("ruSt", "second").unwrap(),
+            chunk_from_strings("IDAT", "more image data").unwrap(),
+            chunk_from_strings("ruSt", "third").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let counts: Vec<(String, usize)> = png.type_counts().into_iter().collect();
+        assert_eq!(
+            counts,
+            vec![
+                (String::from("IDAT"), 2),
+                (String::from("IEND"), 1),
+                (String::from("IHDR"), 1),
+                (String::from("ruSt"), 3),
+            ]
+        );
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -238,6 +1644,7 @@ mod tests {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
         let expected: Vec<u8> = PNG_FILE.to_vec();
+        assert_eq!(actual.len(), png.len_on_disk());
         assert_eq!(actual, expected);
     }
 